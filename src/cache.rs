@@ -0,0 +1,198 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::SystemTime,
+};
+
+use anyhow::Context as _;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{FolderSize, Inode},
+    utility,
+};
+
+/// Cache of target directory sizes, so unchanged targets do not have to be walked again.
+///
+/// An entry is keyed by the canonical target path and the mtime of every directory
+/// below it. The mtimes of the top-level directories alone are not enough, as
+/// `cargo build` adds files to existing directories like `target/debug/deps`,
+/// which only changes the mtime of that directory.
+/// Files that are rewritten in place without touching their directory are not noticed.
+#[derive(Default, Debug)]
+pub struct SizeCache {
+    /// The file the cache is loaded from and saved to, `None` if the cache is disabled
+    path: Option<PathBuf>,
+    entries: RwLock<HashMap<PathBuf, CachedSize>>,
+    changed: AtomicBool,
+}
+
+/// Version of the cached sizes, entries of other versions were measured differently
+pub const CACHE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+struct CacheFile {
+    version: u32,
+    entries: Vec<CachedSize>,
+}
+
+/// The size of a target directory, with the directories it had when it was walked
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CachedSize {
+    path: PathBuf,
+    /// Directories relative to the target directory and their mtime, sorted by path
+    dirs: Vec<(PathBuf, Option<SystemTime>)>,
+    bytes: u64,
+    disk_bytes: u64,
+    linked: Vec<(Inode, u64, u64)>,
+    hardlinked_bytes: u64,
+    hardlinked_disk_bytes: u64,
+    newest: Option<SystemTime>,
+}
+
+impl SizeCache {
+    /// Loads the cache stored next to the project index, or returns a disabled cache
+    pub fn for_index(index_path: &Path, enabled: bool) -> anyhow::Result<Self> {
+        if enabled {
+            Self::load(&index_path.with_file_name("sizes.json"))
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Loads the cache from the given file, returning an empty cache if it does not exist
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = if path.exists() {
+            let content = fs::read_to_string(path)
+                .context(format!("Failed to read size cache: {}", path.display()))?;
+            serde_json::from_str(&content).unwrap_or_else(|error| {
+                log::warn!("Ignoring corrupt size cache {}: {error}", path.display());
+                CacheFile::default()
+            })
+        } else {
+            CacheFile::default()
+        };
+        let entries = if file.version == CACHE_VERSION {
+            file.entries
+        } else {
+            log::debug!("Ignoring size cache of version {}", file.version);
+            vec![]
+        };
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            entries: RwLock::new(
+                entries
+                    .into_iter()
+                    .map(|entry| (entry.path.clone(), entry))
+                    .collect(),
+            ),
+            changed: AtomicBool::new(false),
+        })
+    }
+
+    /// Writes the cache back if it changed, dropping entries of removed target directories
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.changed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let mut entries: Vec<_> = self
+            .entries
+            .read()
+            .values()
+            .filter(|entry| entry.path.exists())
+            .cloned()
+            .collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context(format!(
+                "Failed to create cache directory: {}",
+                parent.display()
+            ))?;
+        }
+        let content = serde_json::to_string(&CacheFile {
+            version: CACHE_VERSION,
+            entries,
+        })
+        .context("Failed to serialize size cache")?;
+        fs::write(path, content).context(format!("Failed to write size cache: {}", path.display()))
+    }
+
+    /// Returns the cached size of a target directory if none of its directories changed
+    pub fn get(&self, target_dir: &Path) -> Option<FolderSize> {
+        self.path.as_ref()?;
+        let key = fs::canonicalize(target_dir).ok()?;
+        let entry = self.entries.read().get(&key)?.clone();
+        if entry.dirs != relative_dirs(&key, utility::dir_mtimes(&key)) {
+            log::debug!("Cached size of {} is outdated", key.display());
+            return None;
+        }
+
+        let mut size = FolderSize {
+            bytes: entry.bytes,
+            disk_bytes: entry.disk_bytes,
+            hardlinked_bytes: entry.hardlinked_bytes,
+            hardlinked_disk_bytes: entry.hardlinked_disk_bytes,
+            newest: entry.newest,
+            ..FolderSize::default()
+        };
+        size.linked = entry
+            .linked
+            .iter()
+            .map(|(inode, bytes, disk_bytes)| (*inode, (*bytes, *disk_bytes)))
+            .collect();
+        Some(size)
+    }
+
+    /// Stores the size of a fully read target directory
+    pub fn insert(&self, target_dir: &Path, size: &FolderSize) {
+        if self.path.is_none() || size.errors > 0 || size.dirs.is_empty() {
+            return;
+        }
+        let Ok(key) = fs::canonicalize(target_dir) else {
+            return;
+        };
+
+        let entry = CachedSize {
+            path: key.clone(),
+            dirs: relative_dirs(target_dir, size.dirs.clone()),
+            bytes: size.bytes,
+            disk_bytes: size.disk_bytes,
+            linked: size
+                .linked
+                .iter()
+                .map(|(inode, (bytes, disk_bytes))| (*inode, *bytes, *disk_bytes))
+                .collect(),
+            hardlinked_bytes: size.hardlinked_bytes,
+            hardlinked_disk_bytes: size.hardlinked_disk_bytes,
+            newest: size.newest,
+        };
+        self.entries.write().insert(key, entry);
+        self.changed.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Makes the directories relative to the target directory and sorts them, so walks can be compared
+fn relative_dirs(
+    target_dir: &Path,
+    dirs: Vec<(PathBuf, Option<SystemTime>)>,
+) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut dirs: Vec<_> = dirs
+        .into_iter()
+        .map(|(dir, modified)| {
+            let relative = dir.strip_prefix(target_dir).unwrap_or(&dir).to_path_buf();
+            (relative, modified)
+        })
+        .collect();
+    dirs.sort_by(|a, b| a.0.cmp(&b.0));
+    dirs
+}
@@ -1,11 +1,15 @@
 pub mod opts;
 
-use clap::Parser;
-use opts::Options;
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use opts::{HomeOptions, Options, StatsOptions, ToolchainsOptions};
 
 use crate::{
     cli::opts::OptionsTrait as _,
-    commands::{clean, executor, stats},
+    commands::{self, clean, executor, home, roots, stats, toolchains},
+    config::Config,
+    utility::{self, UserDirs},
 };
 
 /// Represents the available commands
@@ -13,7 +17,7 @@ use crate::{
 #[command(author, version, about)]
 pub enum Commands {
     /// Print statistics about all Rust projects in the directory
-    Stats(Options),
+    Stats(StatsOptions),
     /// Execute `cargo build` on all Rust projects in the directory
     Build(Options),
     /// Execute `cargo check` on all Rust projects in the directory
@@ -30,6 +34,32 @@ pub enum Commands {
     Bench(Options),
     /// Execute `cargo update` on all Rust projects in the directory
     Update(Options),
+    /// Index all Rust projects in the directory to speed up later commands
+    Index(Options),
+    /// Manage the default search paths used when no `--path` is given
+    #[command(subcommand)]
+    Roots(RootsCommand),
+    /// Show and clean the registries and git dependencies in the cargo home
+    Home(HomeOptions),
+    /// List the installed rustup toolchains and the projects that pin them
+    Toolchains(ToolchainsOptions),
+}
+
+/// Represents the subcommands of `cargo wash roots`
+#[derive(Subcommand)]
+pub enum RootsCommand {
+    /// Register a directory as a default search path
+    Add {
+        /// Path to the directory to register
+        path: PathBuf,
+    },
+    /// Remove a directory from the default search paths
+    Remove {
+        /// Path to the directory to remove
+        path: PathBuf,
+    },
+    /// List the registered default search paths
+    List,
 }
 
 impl Commands {
@@ -42,35 +72,63 @@ impl Commands {
             Self::Test(_) => "test",
             Self::Bench(_) => "bench",
             Self::Update(_) => "update",
-            Self::Stats(_) | Self::Clean(_) => "none",
+            Self::Stats(_)
+            | Self::Clean(_)
+            | Self::Index(_)
+            | Self::Roots(_)
+            | Self::Home(_)
+            | Self::Toolchains(_) => "none",
         }
     }
 }
 
 impl Commands {
-    const fn opts(&self) -> &Options {
+    const fn opts(&self) -> Option<&Options> {
         match self {
-            Self::Stats(opts)
-            | Self::Build(opts)
+            Self::Stats(stats) => Some(&stats.options),
+            Self::Toolchains(toolchains) => Some(&toolchains.options),
+            Self::Build(opts)
             | Self::Check(opts)
             | Self::Doc(opts)
             | Self::Clean(opts)
             | Self::Run(opts)
             | Self::Test(opts)
             | Self::Bench(opts)
-            | Self::Update(opts) => opts,
+            | Self::Update(opts)
+            | Self::Index(opts) => Some(opts),
+            Self::Roots(_) | Self::Home(_) => None,
         }
     }
 
-    /// Run the command based on the provided options
-    pub fn run(&self) -> anyhow::Result<()> {
-        let projects = self.opts().check_args()?;
+    /// Run the command based on the provided options, keeping the files of cargo-wash in `dirs`
+    pub fn run(&self, dirs: &UserDirs) -> anyhow::Result<()> {
+        if let Some(threads) = self.opts().and_then(|opts| opts.threads) {
+            utility::init_thread_pool(threads);
+        }
+
         match self {
-            Self::Stats(_) => {
-                stats::show(&projects);
+            Self::Index(opts) => {
+                commands::index::run(opts, dirs)?;
+            }
+            Self::Roots(command) => {
+                roots::run(command, &Config::path(dirs))?;
+            }
+            Self::Home(opts) => {
+                home::run(opts, &utility::cargo_home()?)?;
+            }
+            Self::Toolchains(opts) => {
+                toolchains::run(
+                    &opts.search_options().check_args(dirs)?,
+                    opts,
+                    &utility::rustup_home()?,
+                    dirs,
+                )?;
+            }
+            Self::Stats(opts) => {
+                stats::show(&opts.check_args(dirs)?, opts, dirs)?;
             }
             Self::Clean(opts) => {
-                clean::run(&projects, opts.exclude.as_ref())?;
+                clean::run(&opts.check_args(dirs)?, opts.size_mode, opts.units)?;
             }
             Self::Build(opts)
             | Self::Run(opts)
@@ -79,7 +137,7 @@ impl Commands {
             | Self::Update(opts)
             | Self::Doc(opts)
             | Self::Check(opts) => {
-                executor::run(&projects, opts, self.to_cargo_command())?;
+                executor::run(&opts.check_args(dirs)?, opts, self.to_cargo_command())?;
             }
         }
         Ok(())
@@ -1,23 +1,100 @@
 use clap::Parser;
+use ignore::WalkBuilder;
 use indicatif::{ProgressBar, ProgressStyle};
 use parking_lot::RwLock;
-use rayon::iter::{ParallelBridge as _, ParallelIterator as _};
+use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fs;
-use std::path::PathBuf;
+use std::iter;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-use crate::{data::Project, utility};
+use crate::{
+    cache::SizeCache,
+    config::Config,
+    data::{self, Project, SizeMode, Units},
+    filter::ProjectFilter,
+    git,
+    history::Since,
+    index::{self, DiscoverySettings, Index, IndexedProject, IndexedRoot},
+    utility::{self, UserDirs},
+};
+
+/// Name of the gitignore-style files that exclude directories from discovery
+pub const WASHIGNORE_FILE: &str = ".washignore";
 
 /// Represents general command line options.
-#[derive(Parser, Default, Clone)]
+#[derive(Parser, Clone)]
+#[expect(clippy::struct_excessive_bools, reason = "Command line flags")]
 pub struct Options {
-    /// Path to the directory from which to start the search for Rust projects
-    #[clap(short, long, default_value = ".")]
-    pub path: PathBuf,
+    /// Path to a directory from which to start the search for Rust projects (repeatable)
+    /// Defaults to the roots registered with `cargo wash roots add`, or the current directory
+    #[clap(short, long, verbatim_doc_comment)]
+    pub path: Vec<PathBuf>,
 
-    /// Exclude the provided folder from the size calculation and cleaning
-    #[clap(long, short)]
-    pub exclude: Option<String>,
+    /// Read newline-separated project paths from a file (or `-` for stdin) instead of searching
+    #[clap(long, value_name = "FILE", conflicts_with = "path")]
+    pub projects_from: Option<PathBuf>,
+
+    /// Only include projects whose package name or path relative to its search root matches the glob (repeatable)
+    #[clap(long, short, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Exclude projects whose package name or path relative to its search root matches the glob (repeatable)
+    #[clap(long, short, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Exclude projects whose package name or path relative to its search root matches the regex (repeatable)
+    #[clap(long, value_name = "REGEX")]
+    pub exclude_regex: Vec<String>,
+
+    /// Maximum directory depth below the path to search for Rust projects
+    #[clap(long, default_value_t = 1)]
+    pub max_depth: usize,
+
+    /// Also respect `.gitignore` files when searching for Rust projects
+    #[clap(long)]
+    pub gitignore: bool,
+
+    /// Do not respect `.washignore` (or `.gitignore`) files when searching for Rust projects
+    #[clap(long, conflicts_with = "gitignore")]
+    pub no_ignore: bool,
+
+    /// Ignore the project index and rescan every search path
+    #[clap(long)]
+    pub refresh: bool,
+
+    /// Only include projects that were last built more than AGE ago (e.g. 30d, 2w)
+    #[clap(long, value_name = "AGE", value_parser = utility::parse_duration)]
+    pub stale: Option<Duration>,
+
+    /// Only include projects in git repositories without uncommitted changes or untracked files
+    /// in the project directory
+    #[clap(long)]
+    pub only_clean_git: bool,
+
+    /// Only include projects in git repositories with uncommitted changes or untracked files
+    /// in the project directory
+    #[clap(long, conflicts_with = "only_clean_git")]
+    pub only_dirty_git: bool,
+
+    /// Recalculate all sizes instead of reusing the sizes of unchanged target directories
+    #[clap(long)]
+    pub no_cache: bool,
+
+    /// How to measure the size of the target directories
+    #[clap(long, value_enum, default_value_t = SizeMode::Apparent)]
+    pub size_mode: SizeMode,
+
+    /// Units used to display sizes
+    #[clap(long, value_enum, default_value_t = Units::Iec)]
+    pub units: Units,
+
+    /// Maximum number of threads used to scan projects and calculate sizes
+    #[clap(long, value_name = "N")]
+    pub threads: Option<usize>,
 
     /// Enable parallel processing of projects
     /// ATTENTION: This may lead to high CPU usage!
@@ -29,79 +106,764 @@ pub struct Options {
     pub args: Vec<String>,
 }
 
+impl Default for Options {
+    fn default() -> Self {
+        Self::parse_from(["cargo-wash"])
+    }
+}
+
 /// A trait defining common fields shared between options structs.
 pub trait CommonOptions {
-    fn path(&self) -> &PathBuf;
-    fn exclude(&self) -> Option<&String>;
+    fn paths(&self) -> &[PathBuf];
+    fn projects_from(&self) -> Option<&PathBuf>;
+    fn include(&self) -> &[String];
+    fn exclude(&self) -> &[String];
+    fn exclude_regex(&self) -> &[String];
+    fn max_depth(&self) -> usize;
+    fn gitignore(&self) -> bool;
+    fn no_ignore(&self) -> bool;
+    fn refresh(&self) -> bool;
+    fn no_cache(&self) -> bool;
+    fn stale(&self) -> Option<Duration>;
+    fn only_clean_git(&self) -> bool;
+    fn only_dirty_git(&self) -> bool;
+    fn size_mode(&self) -> SizeMode;
 }
 
 impl CommonOptions for Options {
-    fn path(&self) -> &PathBuf {
+    fn paths(&self) -> &[PathBuf] {
         &self.path
     }
 
-    fn exclude(&self) -> Option<&String> {
-        self.exclude.as_ref()
+    fn projects_from(&self) -> Option<&PathBuf> {
+        self.projects_from.as_ref()
+    }
+
+    fn include(&self) -> &[String] {
+        &self.include
+    }
+
+    fn exclude(&self) -> &[String] {
+        &self.exclude
+    }
+
+    fn exclude_regex(&self) -> &[String] {
+        &self.exclude_regex
+    }
+
+    fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    fn gitignore(&self) -> bool {
+        self.gitignore
+    }
+
+    fn no_ignore(&self) -> bool {
+        self.no_ignore
+    }
+
+    fn refresh(&self) -> bool {
+        self.refresh
+    }
+
+    fn no_cache(&self) -> bool {
+        self.no_cache
+    }
+
+    fn stale(&self) -> Option<Duration> {
+        self.stale
+    }
+
+    fn only_clean_git(&self) -> bool {
+        self.only_clean_git
+    }
+
+    fn only_dirty_git(&self) -> bool {
+        self.only_dirty_git
+    }
+
+    fn size_mode(&self) -> SizeMode {
+        self.size_mode
+    }
+}
+
+/// Represents the options of the `stats` command.
+#[derive(Parser, Clone, Default)]
+#[expect(clippy::struct_excessive_bools, reason = "Command line flags")]
+pub struct StatsOptions {
+    #[clap(flatten)]
+    pub options: Options,
+
+    /// Show additional columns, like the apparent and on-disk size, hard-linked space
+    /// and the toolchains the artifacts were built with
+    #[clap(long, short)]
+    pub long: bool,
+
+    /// Show the branch, uncommitted changes and distance to the upstream branch
+    /// of the git repository each project is in
+    #[clap(long)]
+    pub git: bool,
+
+    /// Output format of the statistics
+    #[clap(long, value_enum, default_value_t = StatsFormat::Table)]
+    pub format: StatsFormat,
+
+    /// Order of the projects
+    #[clap(long, value_enum, default_value_t = StatsSort::Name)]
+    pub sort: StatsSort,
+
+    /// Reverse the order of the projects
+    #[clap(long, short)]
+    pub reverse: bool,
+
+    /// Only show the first N projects, the total still covers all projects
+    #[clap(long, value_name = "N")]
+    pub top: Option<usize>,
+
+    /// Group the projects by their directories below the search path,
+    /// with the aggregated size of each directory, largest first
+    #[clap(long, conflicts_with_all = ["format", "sort", "reverse", "top"])]
+    pub tree: bool,
+
+    /// Collapse the tree below this many directory levels
+    #[clap(long, value_name = "N", requires = "tree")]
+    pub depth: Option<usize>,
+
+    /// Only show the project with this package name or id
+    #[clap(long, value_name = "NAME")]
+    pub project: Option<String>,
+
+    /// Break the target directory of the `--project` down by profile, target triple
+    /// and kind of build output, and list the largest files (limited by `--top`, default 10)
+    #[clap(long, requires = "project", conflicts_with_all = ["format", "tree"])]
+    pub breakdown: bool,
+
+    /// Sum up the compiled artifacts in `deps` and `.fingerprint` per dependency crate
+    /// and version over all projects, largest first (limited by `--top`)
+    #[clap(long, conflicts_with_all = ["format", "tree", "breakdown"])]
+    pub crates: bool,
+
+    /// Append a snapshot of the project sizes to the size history
+    #[clap(long)]
+    pub record: bool,
+
+    /// Compare the sizes with the latest recorded snapshot,
+    /// or with the latest one that is at least SINCE old (e.g. 7d, 12h, 2w)
+    #[clap(
+        long,
+        value_name = "SINCE",
+        num_args = 0..=1,
+        default_missing_value = "latest",
+        value_parser = Since::parse,
+        conflicts_with_all = ["format", "tree", "breakdown", "crates"],
+    )]
+    pub diff: Option<Since>,
+}
+
+/// Represents the orders of the projects in the `stats` command.
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum StatsSort {
+    /// By package name, case-insensitive
+    #[default]
+    Name,
+    /// Largest target directory first
+    Size,
+    /// Least recently built first
+    Age,
+    /// By project path
+    Path,
+}
+
+/// Represents the output formats of the `stats` command.
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum StatsFormat {
+    /// A table for the terminal
+    #[default]
+    Table,
+    /// A JSON document with raw byte counts
+    Json,
+    /// One comma-separated line per project with raw byte counts
+    Csv,
+    /// A Markdown table, e.g. for pull request comments
+    Markdown,
+}
+
+/// Represents the options of the `home` command.
+#[derive(Parser, Clone, Default)]
+#[command(args_conflicts_with_subcommands = true)]
+#[expect(clippy::struct_excessive_bools, reason = "Command line flags")]
+pub struct HomeOptions {
+    #[command(subcommand)]
+    pub command: Option<HomeCommand>,
+
+    /// Remove the extracted sources of downloaded crates,
+    /// cargo extracts them again from the downloaded `.crate` files
+    #[clap(long)]
+    pub clean_src: bool,
+
+    /// Remove the checkouts of git dependencies,
+    /// cargo checks them out again from the git databases
+    #[clap(long)]
+    pub clean_checkouts: bool,
+
+    /// Remove the downloaded `.crate` files, they are downloaded again when needed
+    #[clap(long)]
+    pub clean_cache: bool,
+
+    /// Only remove `.crate` files that were downloaded more than AGE ago (e.g. 90d)
+    #[clap(long, value_name = "AGE", value_parser = utility::parse_duration, requires = "clean_cache")]
+    pub older_than: Option<Duration>,
+
+    /// Only show what would be removed
+    #[clap(long, global = true)]
+    pub dry_run: bool,
+
+    /// Remove without asking for confirmation
+    #[clap(long, short, global = true)]
+    pub yes: bool,
+
+    /// How to measure the size of the directories
+    #[clap(long, value_enum, default_value_t = SizeMode::Apparent, global = true)]
+    pub size_mode: SizeMode,
+
+    /// Units used to display sizes
+    #[clap(long, value_enum, default_value_t = Units::Iec, global = true)]
+    pub units: Units,
+}
+
+/// Represents the subcommands of `cargo wash home`
+#[derive(clap::Subcommand, Clone)]
+pub enum HomeCommand {
+    /// Remove downloaded crates and extracted sources that were not used for a while,
+    /// based on the last-use tracking of cargo
+    Gc {
+        /// Remove what was not used for more than AGE (e.g. 90d)
+        #[clap(long, value_name = "AGE", value_parser = utility::parse_duration)]
+        max_age: Duration,
+    },
+    /// List the tools installed with `cargo install`, with their size and when they were last used
+    Bins {
+        /// Uninstall the tools whose binaries were not run for more than AGE (e.g. 180d).
+        /// This relies on the file system tracking access times: tools not accessed since
+        /// they were installed are skipped, as on `noatime` mounts their use is unknown
+        #[clap(long, value_name = "AGE", value_parser = utility::parse_duration)]
+        prune_unused: Option<Duration>,
+
+        /// Also uninstall tools not accessed since they were installed,
+        /// only use this if the file system updates access times
+        #[clap(long, requires = "prune_unused")]
+        trust_atime: bool,
+    },
+}
+
+/// Represents the options of the `toolchains` command.
+#[derive(Parser, Clone, Default)]
+pub struct ToolchainsOptions {
+    #[clap(flatten)]
+    pub options: Options,
+
+    /// Uninstall the toolchains that no project pins and that are not the default.
+    /// Needs `--path`, `--projects-from` or registered roots, so every project is considered.
+    /// The filters and ignore files do not apply, a search cut short by `--max-depth` is refused
+    /// and linked or custom toolchains are never uninstalled
+    #[clap(long)]
+    pub remove_unreferenced: bool,
+
+    /// Only show what would be uninstalled
+    #[clap(long, requires = "remove_unreferenced")]
+    pub dry_run: bool,
+
+    /// Uninstall without asking for confirmation
+    #[clap(long, short)]
+    pub yes: bool,
+}
+
+impl ToolchainsOptions {
+    /// Returns the options to search the projects with. Removing toolchains has to see
+    /// every pin, so the filters and ignore files only apply when listing them.
+    pub fn search_options(&self) -> Options {
+        if !self.remove_unreferenced {
+            return self.options.clone();
+        }
+        Options {
+            include: vec![],
+            exclude: vec![],
+            exclude_regex: vec![],
+            stale: None,
+            only_clean_git: false,
+            only_dirty_git: false,
+            gitignore: false,
+            no_ignore: true,
+            ..self.options.clone()
+        }
+    }
+}
+
+impl CommonOptions for StatsOptions {
+    fn paths(&self) -> &[PathBuf] {
+        self.options.paths()
+    }
+
+    fn projects_from(&self) -> Option<&PathBuf> {
+        self.options.projects_from()
+    }
+
+    fn include(&self) -> &[String] {
+        self.options.include()
+    }
+
+    fn exclude(&self) -> &[String] {
+        self.options.exclude()
+    }
+
+    fn exclude_regex(&self) -> &[String] {
+        self.options.exclude_regex()
+    }
+
+    fn max_depth(&self) -> usize {
+        self.options.max_depth()
+    }
+
+    fn gitignore(&self) -> bool {
+        self.options.gitignore()
+    }
+
+    fn no_ignore(&self) -> bool {
+        self.options.no_ignore()
+    }
+
+    fn refresh(&self) -> bool {
+        self.options.refresh()
+    }
+
+    fn no_cache(&self) -> bool {
+        self.options.no_cache()
+    }
+
+    fn stale(&self) -> Option<Duration> {
+        self.options.stale()
+    }
+
+    fn only_clean_git(&self) -> bool {
+        self.options.only_clean_git()
+    }
+
+    fn only_dirty_git(&self) -> bool {
+        self.options.only_dirty_git()
+    }
+
+    fn size_mode(&self) -> SizeMode {
+        self.options.size_mode()
     }
 }
 
 /// A trait for validating options and collecting Rust projects.
 pub trait OptionsTrait {
-    fn check_args(&self) -> anyhow::Result<Vec<Project>>;
+    /// Collects the Rust projects below the search paths, using the project index if available
+    fn check_args(&self, dirs: &UserDirs) -> anyhow::Result<Vec<Project>> {
+        self.scan(dirs, false)
+    }
+
+    /// Collects the Rust projects below the search paths, or the registered roots in `dirs`.
+    /// Roots present in the project index are revalidated incrementally and updated,
+    /// with `rebuild` every root is walked from scratch and (re)added to the index.
+    /// Sizes of unchanged target directories are reused from the size cache next to the index.
+    fn scan(&self, dirs: &UserDirs, rebuild: bool) -> anyhow::Result<Vec<Project>>;
 }
 
 impl<T> OptionsTrait for T
 where
     T: CommonOptions + Send + Sync,
 {
-    fn check_args(&self) -> anyhow::Result<Vec<Project>> {
+    fn scan(&self, dirs: &UserDirs, rebuild: bool) -> anyhow::Result<Vec<Project>> {
+        let index_path = &Index::path(dirs);
         let projects: Arc<RwLock<Vec<Project>>> = Arc::new(RwLock::new(vec![]));
-        let path = utility::sanitize_path_input(self.path())?;
+        let filter = if rebuild {
+            ProjectFilter::default()
+        } else {
+            ProjectFilter::new(self.include(), self.exclude(), self.exclude_regex())?
+        };
 
-        if path.is_dir() {
-            utility::get_project(&path, self.exclude()).map(|p_opt| {
-                if let Some(project) = p_opt {
-                    projects.write().push(project);
+        let cache = SizeCache::for_index(index_path, !self.no_cache())?;
+
+        if let Some(source) = self.projects_from() {
+            let paths = utility::read_project_list(source)?;
+            let projects = load_projects(&paths, &filter, self.size_mode(), &cache)?;
+            cache.save()?;
+            return Ok(finish(projects, self));
+        }
+
+        let settings = DiscoverySettings {
+            max_depth: self.max_depth(),
+            gitignore: self.gitignore(),
+            no_ignore: self.no_ignore(),
+        };
+        let mut index = Index::load(index_path)?;
+        let mut index_changed = false;
+
+        let roots = scan_roots(self.paths(), &Config::path(dirs))?;
+        for root in &roots {
+            let path = utility::sanitize_path_input(root)?;
+
+            if !path.is_dir() {
+                anyhow::bail!("The provided path is not a directory: {}", path.display());
+            }
+            // Ids are relative to the root, with several roots they start with its name
+            let id_base = path.parent().filter(|_| roots.len() > 1).unwrap_or(&path);
+
+            let key = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            let track = rebuild || index.root(&key).is_some();
+            let indexed = index.root(&key).filter(|_| !rebuild && !self.refresh());
+            let cached = indexed.filter(|indexed| indexed.is_fresh(&key, settings));
+            let updated: RwLock<Vec<IndexedProject>> = RwLock::new(vec![]);
+
+            // Reuse the indexed size if the project did not change, otherwise recompute it
+            let resolve = |candidate: &PathBuf| -> anyhow::Result<Option<Project>> {
+                let relative = candidate.strip_prefix(&path).unwrap_or(candidate);
+                let target_dir = key.join(relative).join("target");
+                // Looked up once, checking the cache entry reads the mtimes of all directories
+                let cached = cache.get(&target_dir);
+
+                if let Some(entry) = indexed
+                    .and_then(|indexed| indexed.project(relative))
+                    .filter(|entry| entry.is_fresh(candidate, self.size_mode()) && cached.is_some())
+                {
+                    let matches =
+                        filter.is_match(&entry.name, &utility::project_id(candidate, &path)?);
+                    // The indexed id may stem from a scan with another number of roots
+                    let mut project = entry.to_project(candidate, self.size_mode());
+                    project.id = utility::project_id(candidate, id_base)?;
+                    project.root = Some(path.clone());
+                    return Ok(matches.then_some(project));
+                }
+
+                let modified = index::modified(candidate);
+                let target_modified = index::modified(&target_dir);
+                let project = utility::get_project(
+                    candidate,
+                    &path,
+                    id_base,
+                    &filter,
+                    self.size_mode(),
+                    &cache,
+                    cached,
+                )?;
+                if track && let Some(project) = &project {
+                    updated.write().push(IndexedProject::new(
+                        relative,
+                        target_dir,
+                        project,
+                        self.size_mode(),
+                        modified,
+                        target_modified,
+                    ));
                 }
-            })?;
-
-            match fs::read_dir(&path) {
-                Ok(entries) => {
-                    let entries_vec: Vec<_> = entries.flatten().collect();
-
-                    // Create progress bar for scanning directories
-                    let pb = ProgressBar::new(entries_vec.len() as u64);
-                    pb.set_style(
-                        ProgressStyle::default_bar()
-                            .template("{spinner:.green} [{bar:40.cyan/blue}] {pos}/{len} {msg}")
-                            .unwrap_or_else(|_| ProgressStyle::default_bar())
-                            .progress_chars("#>-"),
-                    );
-                    pb.set_message("Scanning projects...");
-
-                    entries_vec.iter().par_bridge().for_each(|entry| {
-                        let pathbuf = entry.path();
-                        if pathbuf.is_dir() {
-                            match utility::get_project(&pathbuf, self.exclude()) {
-                                Ok(p_opt) => {
-                                    if let Some(project) = p_opt {
-                                        projects.write().push(project);
-                                    }
-                                }
-                                Err(error) => log::warn!("Error checking project: {error}"),
-                            }
-                        }
-                        pb.inc(1);
-                    });
-
-                    pb.finish_and_clear();
+                Ok(project)
+            };
+
+            projects.write().extend(resolve(&path)?);
+
+            let (candidates, walked) = list_candidates(&path, cached, self);
+
+            let pb = progress_bar(candidates.len());
+
+            candidates.par_iter().for_each(|pathbuf| {
+                match resolve(pathbuf) {
+                    Ok(project) => projects.write().extend(project),
+                    Err(error) => log::warn!("Error checking project: {error}"),
+                }
+                pb.inc(1);
+            });
+
+            pb.finish_and_clear();
+
+            let updated = updated.into_inner();
+            if track && (walked.is_some() || !updated.is_empty()) {
+                let entry = index.root_mut(&key, settings);
+                if let Some(dirs) = walked {
+                    entry.set_dirs(&path, &dirs, &candidates);
                 }
-                Err(error) => log::warn!("Error reading directory: {error}"),
+                entry.upsert(updated);
+                index_changed = true;
             }
+        }
+
+        if index_changed {
+            index.save(index_path)?;
+        }
+        cache.save()?;
+
+        Ok(finish(projects.read().to_vec(), self))
+    }
+}
+
+/// Creates the progress bar shown while the projects below a root are checked
+fn progress_bar(len: usize) -> ProgressBar {
+    let pb = ProgressBar::new(len as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("#>-"),
+    );
+    pb.set_message("Scanning projects...");
+    pb
+}
+
+/// Removes duplicate projects and the projects excluded by `--stale` and the git filters,
+/// then sets the space the remaining projects share through hard links
+fn finish<T: CommonOptions>(projects: Vec<Project>, options: &T) -> Vec<Project> {
+    let now = SystemTime::now();
+    let mut projects = dedup_projects(projects);
+    unique_ids(&mut projects);
+    let mut projects: Vec<_> = projects
+        .into_iter()
+        .filter(|project| {
+            options.stale().is_none_or(|stale| {
+                project
+                    .last_built
+                    .and_then(|time| now.duration_since(time).ok())
+                    .is_some_and(|age| age >= stale)
+            })
+        })
+        .collect();
+
+    if options.only_clean_git() || options.only_dirty_git() {
+        git::read_all(&mut projects);
+        // Projects outside of a repository, or where git failed, match neither filter
+        let dirty = options.only_dirty_git();
+        projects.retain(|project| project.git.as_ref().and_then(|git| git.dirty) == Some(dirty));
+    }
+
+    data::annotate_shared_links(&mut projects);
+    projects
+}
+
+/// Builds the projects for an explicit list of paths, without searching for projects.
+/// Paths that are missing or are no Rust projects are reported together.
+fn load_projects(
+    paths: &[PathBuf],
+    filter: &ProjectFilter,
+    size_mode: SizeMode,
+    cache: &SizeCache,
+) -> anyhow::Result<Vec<Project>> {
+    let root = utility::sanitize_path_input(&PathBuf::from("."))?;
+    let projects: RwLock<Vec<Project>> = RwLock::new(vec![]);
+    let warnings: RwLock<Vec<String>> = RwLock::new(vec![]);
+
+    paths.par_iter().for_each(|path| {
+        let warning = if !path.exists() {
+            Some("path does not exist".to_owned())
+        } else if !path.is_dir() {
+            Some("not a directory".to_owned())
+        } else if !path.join("Cargo.toml").exists() {
+            Some("not a Rust project".to_owned())
         } else {
-            anyhow::bail!("The provided path is not a directory.");
+            let cached = cache.get(&path.join("target"));
+            match utility::get_project(path, &root, &root, filter, size_mode, cache, cached) {
+                Ok(Some(project)) => {
+                    projects.write().push(project);
+                    None
+                }
+                Ok(None) => None,
+                Err(error) => Some(format!("{error:#}")),
+            }
+        };
+        if let Some(warning) = warning {
+            warnings
+                .write()
+                .push(format!("{}: {warning}", path.display()));
+        }
+    });
+
+    let mut warnings = warnings.into_inner();
+    if !warnings.is_empty() {
+        warnings.sort();
+        log::warn!(
+            "Skipped {} of {} listed paths:\n  {}",
+            warnings.len(),
+            paths.len(),
+            warnings.join("\n  ")
+        );
+    }
+
+    Ok(projects.into_inner())
+}
+
+/// Lists the directories to check below a root: the indexed projects if the index
+/// is still complete, otherwise the result of a new walk, together with the
+/// walked directories to record in the index
+fn list_candidates<T: CommonOptions>(
+    path: &Path,
+    cached: Option<&IndexedRoot>,
+    options: &T,
+) -> (Vec<PathBuf>, Option<Vec<PathBuf>>) {
+    if let Some(cached) = cached {
+        log::debug!("Using project index for {}", cached.path.display());
+        let candidates = cached
+            .projects
+            .iter()
+            .filter(|p| !p.path.as_os_str().is_empty())
+            .map(|p| path.join(&p.path))
+            .collect();
+        return (candidates, None);
+    }
+
+    let candidates = discover_dirs(path, options);
+    let mut dirs = vec![path.to_path_buf()];
+    dirs.extend(
+        candidates
+            .iter()
+            .filter(|dir| !dir.join("Cargo.toml").exists())
+            .cloned(),
+    );
+    (candidates, Some(dirs))
+}
+
+/// Returns the given paths, or the roots registered in the config if none were given,
+/// falling back to the current directory
+pub fn scan_roots(paths: &[PathBuf], config_path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !paths.is_empty() {
+        return Ok(paths.to_vec());
+    }
+
+    let roots: Vec<_> = Config::load(config_path)?
+        .roots
+        .into_iter()
+        .filter(|root| {
+            let exists = root.is_dir();
+            if !exists {
+                log::warn!("Skipping missing root: {}", root.display());
+            }
+            exists
+        })
+        .collect();
+
+    if roots.is_empty() {
+        Ok(vec![PathBuf::from(".")])
+    } else {
+        Ok(roots)
+    }
+}
+
+/// Returns the directories at `--max-depth` below the search roots that were not searched,
+/// although they have subdirectories that may hold more projects.
+/// Hidden directories and directories inside of a project are left out.
+pub fn depth_limited<T: CommonOptions>(
+    options: &T,
+    dirs: &UserDirs,
+) -> anyhow::Result<Vec<PathBuf>> {
+    if options.projects_from().is_some() {
+        return Ok(vec![]);
+    }
+    let is_hidden = |name: &OsStr| name.to_string_lossy().starts_with('.');
+    let has_subdirs = |dir: &Path| {
+        fs::read_dir(dir).is_ok_and(|entries| {
+            entries.flatten().any(|entry| {
+                entry.file_type().is_ok_and(|t| t.is_dir()) && !is_hidden(&entry.file_name())
+            })
+        })
+    };
+
+    let mut limited = vec![];
+    for root in scan_roots(options.paths(), &Config::path(dirs))? {
+        let root = utility::sanitize_path_input(&root)?;
+        let in_project = |dir: &Path| {
+            dir.ancestors()
+                .skip(1)
+                .take_while(|ancestor| ancestor.starts_with(&root))
+                .any(|ancestor| ancestor.join("Cargo.toml").exists())
+        };
+        let walked = discover_dirs(&root, options);
+        limited.extend(
+            iter::once(root.clone())
+                .chain(walked)
+                .filter(|dir| {
+                    dir.strip_prefix(&root).is_ok_and(|relative| {
+                        relative.components().count() == options.max_depth()
+                            && !relative.iter().any(is_hidden)
+                    })
+                })
+                .filter(|dir| {
+                    !dir.join("Cargo.toml").exists() && !in_project(dir) && has_subdirs(dir)
+                }),
+        );
+    }
+    Ok(limited)
+}
+
+/// Removes projects found under several roots, comparing their canonical paths
+fn dedup_projects(projects: Vec<Project>) -> Vec<Project> {
+    let mut seen = HashSet::new();
+    projects
+        .into_iter()
+        .filter(|project| {
+            seen.insert(fs::canonicalize(&project.path).unwrap_or_else(|_| project.path.clone()))
+        })
+        .collect()
+}
+
+/// Replaces ids that several projects share, like the same directory below roots
+/// with the same name, with the full paths of the projects
+fn unique_ids(projects: &mut [Project]) {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for project in projects.iter() {
+        *counts.entry(project.id.clone()).or_default() += 1;
+    }
+    for project in projects {
+        if counts.get(&project.id).is_some_and(|count| *count > 1) {
+            project.id = project.path.display().to_string();
         }
+    }
+}
+
+/// Walk the directories below `root` up to `--max-depth`,
+/// honouring `.washignore` (and optionally `.gitignore`) files.
+/// The walk does not descend into Rust projects other than the root.
+fn discover_dirs<T: CommonOptions>(root: &Path, options: &T) -> Vec<PathBuf> {
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .standard_filters(false)
+        .follow_links(true)
+        .max_depth(Some(options.max_depth()));
 
-        Ok(projects.read().to_vec())
+    if !options.no_ignore() {
+        builder.add_custom_ignore_filename(WASHIGNORE_FILE);
+        if options.gitignore() {
+            builder
+                .git_ignore(true)
+                .git_exclude(true)
+                .require_git(false);
+        }
     }
+
+    let root_dir = root.to_path_buf();
+    builder.filter_entry(move |entry| {
+        if entry.depth() == 0 {
+            return true;
+        }
+        let Some(parent) = entry.path().parent() else {
+            return true;
+        };
+        let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+        let in_project = parent.join("Cargo.toml").exists();
+        if parent == root_dir {
+            is_dir && !(in_project && entry.file_name() == "target")
+        } else {
+            is_dir && !in_project
+        }
+    });
+
+    builder
+        .build()
+        .filter_map(|entry| match entry {
+            Ok(entry) if entry.depth() > 0 => Some(entry.into_path()),
+            Ok(_) => None,
+            Err(error) => {
+                log::warn!("Error reading directory: {error}");
+                None
+            }
+        })
+        .collect()
 }
@@ -0,0 +1,297 @@
+use std::{
+    collections::BTreeMap,
+    env::consts::EXE_SUFFIX,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context as _;
+use comfy_table::{Table, presets::UTF8_FULL_CONDENSED};
+use serde::Deserialize;
+
+use crate::{
+    cli::opts::HomeOptions,
+    data::{Size, SizeMode},
+    utility,
+};
+
+/// Metadata of `cargo install`, keyed by package id like `ripgrep 14.1.0 (registry+...)`
+#[derive(Deserialize)]
+struct CratesJson {
+    installs: BTreeMap<String, InstallInfo>,
+}
+
+#[derive(Deserialize)]
+struct InstallInfo {
+    bins: Vec<String>,
+}
+
+/// The older metadata of `cargo install`, written next to `.crates2.json`
+#[derive(Deserialize)]
+struct CratesToml {
+    v1: BTreeMap<String, Vec<String>>,
+}
+
+/// A package installed with `cargo install`
+pub struct Tool {
+    pub name: String,
+    pub version: String,
+    /// `crates.io`, another registry, `git` or `path`
+    pub source: String,
+    pub bins: Vec<PathBuf>,
+    pub size: u64,
+    /// Modification time of the newest binary
+    pub installed: Option<SystemTime>,
+    /// Access time of the most recently run binary, `None` if no binary was accessed
+    /// since it was installed, which is all `noatime` mounts show
+    pub last_used: Option<SystemTime>,
+}
+
+/// Lists the installed tools and uninstalls the ones not used for `prune_unused`.
+/// With `trust_atime` an access time from the installation counts as last use.
+#[expect(clippy::print_stdout, reason = "No other way to show the tools")]
+pub fn run(
+    options: &HomeOptions,
+    prune_unused: Option<Duration>,
+    trust_atime: bool,
+    cargo_home: &Path,
+) -> anyhow::Result<()> {
+    let tools = tools(cargo_home, options.size_mode, trust_atime)?;
+    let now = SystemTime::now();
+    println!("{}", render(&tools, options, now));
+
+    let Some(max_age) = prune_unused else {
+        return Ok(());
+    };
+    let unknown = tools.iter().filter(|tool| tool.last_used.is_none()).count();
+    if unknown > 0 {
+        log::info!(
+            "Skipping {unknown} tools that were not run since they were installed, \
+             or the file system does not track access times (see --trust-atime)"
+        );
+    }
+    let unused = unused(&tools, max_age, now);
+    if unused.is_empty() {
+        log::info!(
+            "Every tool was used within {}",
+            utility::format_duration(max_age)
+        );
+        return Ok(());
+    }
+    let names: Vec<&str> = unused.iter().map(|tool| tool.name.as_str()).collect();
+    let size = Size::with_units(unused.iter().map(|tool| tool.size).sum(), options.units);
+    if options.dry_run {
+        log::info!("Would uninstall {} and free {size}", names.join(", "));
+        return Ok(());
+    }
+    if !utility::confirm(
+        &format!("Uninstall {} and free {size}?", names.join(", ")),
+        options.yes,
+    )? {
+        log::info!("Nothing uninstalled");
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for tool in unused {
+        if cfg!(test) {
+            log::debug!("Would uninstall: {}", tool.name);
+            continue;
+        }
+        let result = Command::new("cargo")
+            .arg("uninstall")
+            .arg("--root")
+            .arg(cargo_home)
+            .arg(&tool.name)
+            .output();
+        match result {
+            Ok(output) if output.status.success() => log::info!("Uninstalled {}", tool.name),
+            Ok(output) => {
+                log::error!(
+                    "Failed to uninstall {}: {}",
+                    tool.name,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                failed += 1;
+            }
+            Err(error) => {
+                log::error!("Failed to uninstall {}: {error}", tool.name);
+                failed += 1;
+            }
+        }
+    }
+    anyhow::ensure!(
+        failed == 0,
+        "Some tools ({failed}) could not be uninstalled"
+    );
+    Ok(())
+}
+
+/// Reads the tools installed into the cargo home from `.crates2.json`, or `.crates.toml`
+/// if cargo did not write the former
+pub fn tools(
+    cargo_home: &Path,
+    size_mode: SizeMode,
+    trust_atime: bool,
+) -> anyhow::Result<Vec<Tool>> {
+    let json_path = cargo_home.join(".crates2.json");
+    let toml_path = cargo_home.join(".crates.toml");
+    let installs: BTreeMap<String, Vec<String>> = if json_path.exists() {
+        let content = fs::read_to_string(&json_path)
+            .context(format!("Failed to read {}", json_path.display()))?;
+        let crates: CratesJson = serde_json::from_str(&content)
+            .context(format!("Failed to parse {}", json_path.display()))?;
+        crates
+            .installs
+            .into_iter()
+            .map(|(id, info)| (id, info.bins))
+            .collect()
+    } else if toml_path.exists() {
+        let content = fs::read_to_string(&toml_path)
+            .context(format!("Failed to read {}", toml_path.display()))?;
+        let crates: CratesToml =
+            toml::from_str(&content).context(format!("Failed to parse {}", toml_path.display()))?;
+        crates.v1
+    } else {
+        log::info!("No tools installed with `cargo install`");
+        BTreeMap::new()
+    };
+
+    let bin_dir = cargo_home.join("bin");
+    Ok(installs
+        .into_iter()
+        .map(|(id, bins)| {
+            let (name, version, source) = split_package_id(&id);
+            let bins: Vec<PathBuf> = bins
+                .iter()
+                .map(|bin| {
+                    let path = bin_dir.join(bin);
+                    if path.exists() {
+                        path
+                    } else {
+                        bin_dir.join(format!("{bin}{EXE_SUFFIX}"))
+                    }
+                })
+                .collect();
+
+            let metadata: Vec<_> = bins
+                .iter()
+                .filter_map(|bin| fs::metadata(bin).ok())
+                .collect();
+            Tool {
+                name,
+                version,
+                source,
+                size: metadata
+                    .iter()
+                    .map(|m| match size_mode {
+                        SizeMode::Apparent => m.len(),
+                        SizeMode::Disk => utility::disk_usage(m),
+                    })
+                    .sum(),
+                installed: metadata.iter().filter_map(|m| m.modified().ok()).max(),
+                last_used: metadata
+                    .iter()
+                    .filter_map(|m| last_access(m, trust_atime))
+                    .max(),
+                bins,
+            }
+        })
+        .collect())
+}
+
+/// Splits a package id like `ripgrep 14.1.0 (registry+https://...)` into the name,
+/// the version and a short name of the source
+pub fn split_package_id(id: &str) -> (String, String, String) {
+    let mut parts = id.splitn(3, ' ');
+    let name = parts.next().unwrap_or_default().to_owned();
+    let version = parts.next().unwrap_or_default().to_owned();
+    let source = parts
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches('(')
+        .trim_end_matches(')');
+    let source = match source.split_once('+') {
+        Some(("registry" | "sparse", url))
+            if url.contains("github.com/rust-lang/crates.io-index")
+                || url.contains("index.crates.io") =>
+        {
+            "crates.io".to_owned()
+        }
+        Some(("registry" | "sparse", url)) => url.to_owned(),
+        Some((kind, _)) => kind.to_owned(),
+        None => source.to_owned(),
+    };
+    (name, version, source)
+}
+
+/// Returns the access time of a binary if it shows when it was last run.
+/// An access time not after the modification time is left from the installation,
+/// the binary was never run or the file system does not update access times.
+/// With `trust_atime` the installation counts as use, a reinstalled or copied binary
+/// can keep an older access time.
+fn last_access(metadata: &fs::Metadata, trust_atime: bool) -> Option<SystemTime> {
+    let accessed = metadata.accessed().ok()?;
+    match metadata.modified().ok() {
+        Some(modified) if accessed <= modified => trust_atime.then_some(modified),
+        _ => Some(accessed),
+    }
+}
+
+/// Returns the tools whose binaries were all not run for `max_age`.
+/// Tools without a known last use are kept.
+fn unused(tools: &[Tool], max_age: Duration, now: SystemTime) -> Vec<&Tool> {
+    tools
+        .iter()
+        .filter(|tool| {
+            tool.last_used
+                .and_then(|time| now.duration_since(time).ok())
+                .is_some_and(|age| age >= max_age)
+        })
+        .collect()
+}
+
+fn render(tools: &[Tool], options: &HomeOptions, now: SystemTime) -> String {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL_CONDENSED);
+    table.set_header([
+        "Tool",
+        "Version",
+        "Source",
+        "Binaries",
+        "Size",
+        "Installed",
+        "Last used",
+    ]);
+    for tool in tools {
+        let bins: Vec<_> = tool
+            .bins
+            .iter()
+            .map(|bin| bin.file_name().unwrap_or_default().to_string_lossy())
+            .collect();
+        table.add_row([
+            tool.name.clone(),
+            tool.version.clone(),
+            tool.source.clone(),
+            bins.join(", "),
+            Size::with_units(tool.size, options.units).to_string(),
+            utility::format_age(tool.installed, now),
+            tool.last_used.map_or_else(
+                || "unknown".to_owned(),
+                |time| utility::format_age(Some(time), now),
+            ),
+        ]);
+    }
+    table.add_row([
+        "Total".to_owned(),
+        String::new(),
+        String::new(),
+        format!("{} tools", tools.len()),
+        Size::with_units(tools.iter().map(|tool| tool.size).sum(), options.units).to_string(),
+        String::new(),
+        String::new(),
+    ]);
+    table.to_string()
+}
@@ -0,0 +1,219 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Component, Path},
+};
+
+use comfy_table::{Table, presets::UTF8_FULL_CONDENSED};
+
+use crate::{
+    cli::opts::StatsOptions,
+    commands::percentage,
+    data::{FileEntry, FolderSize, Inode, Project, Size, SizeMode},
+    utility::{self, subdirs},
+};
+
+/// Directories of a profile that are listed as their own kind of build output
+const KINDS: [&str; 4] = ["deps", "build", "incremental", ".fingerprint"];
+
+/// Number of files listed if `--top` is not given
+const DEFAULT_LARGEST: usize = 10;
+
+/// The sizes of the parts of a target directory
+#[derive(Default)]
+struct Breakdown {
+    total: FolderSize,
+    /// Profiles like `debug` or `release`, summed over all target triples
+    profiles: BTreeMap<String, FolderSize>,
+    /// `host` for the output of the host platform, or the target triple
+    triples: BTreeMap<String, FolderSize>,
+    /// `deps`, `build`, `incremental`, `.fingerprint`, `doc` or `other`
+    kinds: BTreeMap<String, FolderSize>,
+    /// The largest files in the size mode, with the number of their other hard links,
+    /// which are not listed again
+    largest: Vec<(FileEntry, usize)>,
+}
+
+impl Breakdown {
+    fn new(target_dir: &Path, largest: usize, size_mode: SizeMode) -> Self {
+        let list = utility::list_files(target_dir);
+        if list.errors > 0 {
+            log::warn!(
+                "Breakdown of {} is partial, {} entries could not be read",
+                target_dir.display(),
+                list.errors
+            );
+        }
+
+        let triples = triple_dirs(target_dir);
+        let profiles = profile_dirs(target_dir, &triples);
+        let is_profile =
+            |triple: &str, name: &str| profiles.contains(&(triple.to_owned(), name.to_owned()));
+        let mut breakdown = Self::default();
+        for file in &list.files {
+            let parts: Vec<String> = file
+                .path
+                .strip_prefix(target_dir)
+                .unwrap_or(&file.path)
+                .components()
+                .filter_map(|c| match c {
+                    Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+                    Component::Prefix(_)
+                    | Component::RootDir
+                    | Component::CurDir
+                    | Component::ParentDir => None,
+                })
+                .collect();
+
+            let (triple, rest) = match parts.split_first() {
+                Some((first, rest)) if triples.contains(first) => (first.as_str(), rest),
+                _ => ("host", parts.as_slice()),
+            };
+            let (profile, kind) = match rest {
+                [doc, _, ..] if doc == "doc" => (None, "doc"),
+                [profile, kind, _, ..] if is_profile(triple, profile) => {
+                    let kind = KINDS
+                        .iter()
+                        .find(|k| *k == kind)
+                        .copied()
+                        .unwrap_or("other");
+                    (Some(profile), kind)
+                }
+                [profile, _, ..] if is_profile(triple, profile) => (Some(profile), "other"),
+                _ => (None, "other"),
+            };
+
+            let add = |sizes: &mut FolderSize| {
+                sizes.add_file(file.inode, file.bytes, file.disk_bytes);
+            };
+            add(&mut breakdown.total);
+            add(breakdown.triples.entry(triple.to_owned()).or_default());
+            add(breakdown.kinds.entry(kind.to_owned()).or_default());
+            if let Some(profile) = profile {
+                add(breakdown.profiles.entry(profile.clone()).or_default());
+            }
+        }
+
+        // Hard links to the same file, like `deps/app-<hash>` and `app`, are listed once
+        let mut links: HashMap<Inode, usize> = HashMap::new();
+        for inode in list.files.iter().filter_map(|file| file.inode) {
+            *links.entry(inode).or_default() += 1;
+        }
+        let size = |file: &FileEntry| match size_mode {
+            SizeMode::Apparent => file.bytes,
+            SizeMode::Disk => file.disk_bytes,
+        };
+        let mut files = list.files;
+        files.sort_by(|a, b| size(b).cmp(&size(a)).then_with(|| a.path.cmp(&b.path)));
+        let mut seen = HashSet::new();
+        breakdown.largest = files
+            .into_iter()
+            .filter(|file| file.inode.is_none_or(|inode| seen.insert(inode)))
+            .take(largest)
+            .map(|file| {
+                let others = file
+                    .inode
+                    .and_then(|inode| links.get(&inode))
+                    .map_or(0, |count| count - 1);
+                (file, others)
+            })
+            .collect();
+        breakdown
+    }
+}
+
+/// Renders the breakdown of the target directory of a project
+pub fn render(project: &Project, options: &StatsOptions) -> String {
+    let target_dir = project.path.join("target");
+    let size_mode = options.options.size_mode;
+    let breakdown = Breakdown::new(
+        &target_dir,
+        options.top.unwrap_or(DEFAULT_LARGEST),
+        size_mode,
+    );
+    let units = options.options.units;
+    let total = breakdown.total.get(size_mode);
+
+    let section = |title: &str, sizes: &BTreeMap<String, FolderSize>| {
+        let mut rows: Vec<_> = sizes
+            .iter()
+            .map(|(name, size)| (name, size.get(size_mode)))
+            .collect();
+        rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+        let mut table = Table::new();
+        table.load_preset(UTF8_FULL_CONDENSED);
+        table.set_header([title, "Size", "Share"]);
+        for (name, bytes) in rows {
+            table.add_row([
+                name.clone(),
+                Size::with_units(bytes, units).to_string(),
+                format!("{:.1}%", percentage(bytes, total)),
+            ]);
+        }
+        table.to_string()
+    };
+
+    let mut largest = Table::new();
+    largest.load_preset(UTF8_FULL_CONDENSED);
+    largest.set_header(["Largest files", "Size"]);
+    for (file, links) in &breakdown.largest {
+        let bytes = match size_mode {
+            SizeMode::Apparent => file.bytes,
+            SizeMode::Disk => file.disk_bytes,
+        };
+        let path = file.path.strip_prefix(&target_dir).unwrap_or(&file.path);
+        let name = match links {
+            0 => path.display().to_string(),
+            1 => format!("{} (+1 hard link)", path.display()),
+            _ => format!("{} (+{links} hard links)", path.display()),
+        };
+        largest.add_row([name, Size::with_units(bytes, units).to_string()]);
+    }
+
+    [
+        section("Profile", &breakdown.profiles),
+        section("Target", &breakdown.triples),
+        section("Kind", &breakdown.kinds),
+        largest.to_string(),
+    ]
+    .join("\n")
+}
+
+/// Returns `true` if the directory holds the output of a build profile
+fn is_profile(dir: &Path) -> bool {
+    dir.join(".fingerprint").is_dir() || dir.join("deps").is_dir()
+}
+
+/// Returns the directories in the target directory that hold the profiles
+/// of a cross-compilation target, like `wasm32-unknown-unknown/debug`
+fn triple_dirs(target_dir: &Path) -> HashSet<String> {
+    subdirs(target_dir)
+        .into_iter()
+        .filter(|name| {
+            let dir = target_dir.join(name);
+            !is_profile(&dir)
+                && subdirs(&dir)
+                    .iter()
+                    .any(|profile| is_profile(&dir.join(profile)))
+        })
+        .collect()
+}
+
+/// Returns the profile directories of the host and every target triple
+fn profile_dirs(target_dir: &Path, triples: &HashSet<String>) -> HashSet<(String, String)> {
+    let mut profiles: HashSet<_> = subdirs(target_dir)
+        .into_iter()
+        .filter(|name| is_profile(&target_dir.join(name)))
+        .map(|name| ("host".to_owned(), name))
+        .collect();
+    for triple in triples {
+        let dir = target_dir.join(triple);
+        profiles.extend(
+            subdirs(&dir)
+                .into_iter()
+                .filter(|name| is_profile(&dir.join(name)))
+                .map(|name| (triple.clone(), name)),
+        );
+    }
+    profiles
+}
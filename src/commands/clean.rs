@@ -4,25 +4,17 @@ use indicatif::{ProgressBar, ProgressStyle};
 use parking_lot::RwLock;
 use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
 
-use crate::{commands::print_status, data::Project};
+use crate::{
+    commands::print_status,
+    data::{Project, SizeMode, Units},
+};
 
-pub fn run(projects: &[Project], exclude: Option<&String>) -> anyhow::Result<i32> {
+pub fn run(projects: &[Project], size_mode: SizeMode, units: Units) -> anyhow::Result<i32> {
     let cleaned_projects = Arc::new(RwLock::new(vec![]));
     let failed_projects = Arc::new(RwLock::new(vec![]));
-    // filter excluded projects
-    let mut projects_to_clean = projects.to_vec();
-
-    if let Some(excluded_projects) = exclude {
-        log::debug!("Excluding folders: {excluded_projects}");
-        excluded_projects
-            .split(',')
-            .for_each(|ex| projects_to_clean.retain(|project| project.name != ex.trim()));
-    } else {
-        log::debug!("No folder excluded");
-    }
 
     // Create progress bar
-    let pb = ProgressBar::new(projects_to_clean.len() as u64);
+    let pb = ProgressBar::new(projects.len() as u64);
     pb.set_style(
         ProgressStyle::default_bar()
             .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
@@ -30,12 +22,12 @@ pub fn run(projects: &[Project], exclude: Option<&String>) -> anyhow::Result<i32
     );
     pb.set_message("Cleaning projects...");
 
-    projects_to_clean.par_iter().for_each(|project| {
+    projects.par_iter().for_each(|project| {
         if cfg!(test) {
-            log::debug!("Would clean: {:?}", project.name);
+            log::debug!("Would clean: {:?}", project.id);
             return;
         }
-        log::debug!("Running `cargo clean` for project: {:?}", project.name);
+        log::debug!("Running `cargo clean` for project: {:?}", project.id);
 
         let result = Command::new("cargo")
             .arg("clean")
@@ -50,14 +42,14 @@ pub fn run(projects: &[Project], exclude: Option<&String>) -> anyhow::Result<i32
                     failed_projects.write().push(project.clone());
                     log::error!(
                         "Failed to clean {}: {}",
-                        project.name,
+                        project.id,
                         String::from_utf8_lossy(&output.stderr)
                     );
-                    log::debug!("inputs were: {projects:?}, {exclude:?}");
+                    log::debug!("inputs were: {projects:?}");
                 }
             }
             Err(e) => {
-                log::error!("Failed to clean {}: {}", project.name, e);
+                log::error!("Failed to clean {}: {}", project.id, e);
             }
         }
         pb.inc(1);
@@ -65,7 +57,7 @@ pub fn run(projects: &[Project], exclude: Option<&String>) -> anyhow::Result<i32
 
     pb.finish_with_message("Cleaning completed");
 
-    print_status(projects, &cleaned_projects.read(), exclude);
+    print_status(projects, &cleaned_projects.read(), size_mode, units);
     if failed_projects.read().is_empty() {
         log::info!("All projects cleaned successfully.");
         Ok(0)
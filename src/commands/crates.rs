@@ -0,0 +1,226 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use comfy_table::{Table, presets::UTF8_FULL_CONDENSED};
+use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
+
+use crate::{
+    cli::opts::StatsOptions,
+    data::{Project, Size, SizeMode},
+    utility,
+};
+
+/// One compiled crate in a target directory, identified by its name and metadata hash
+#[derive(Default)]
+struct Unit {
+    /// Package name from the registry, if the dep-info file has the sources
+    package: Option<String>,
+    version: Option<String>,
+    /// The dep-info file has neither registry nor git sources, like the crates of the workspace
+    local: bool,
+    bytes: u64,
+}
+
+/// A dependency crate and version summed up over all projects
+struct CrateUsage {
+    name: String,
+    version: String,
+    projects: HashSet<String>,
+    bytes: u64,
+}
+
+/// Renders how much space each dependency crate takes up in the target directories of all projects
+pub fn render(projects: &[Project], options: &StatsOptions) -> String {
+    let size_mode = options.options.size_mode;
+    let units: Vec<_> = projects
+        .par_iter()
+        .map(|project| {
+            (
+                project.id.clone(),
+                compiled_units(&project.path.join("target"), size_mode),
+            )
+        })
+        .collect();
+
+    let mut usages: HashMap<(String, String), CrateUsage> = HashMap::new();
+    for (id, project_units) in units {
+        for ((name, _), unit) in project_units.into_iter().filter(|(_, unit)| !unit.local) {
+            let name = unit.package.unwrap_or(name);
+            let version = unit.version.unwrap_or_else(|| "-".to_owned());
+            let usage = usages
+                .entry((name.replace('-', "_"), version.clone()))
+                .or_insert_with(|| CrateUsage {
+                    name,
+                    version,
+                    projects: HashSet::new(),
+                    bytes: 0,
+                });
+            usage.projects.insert(id.clone());
+            usage.bytes += unit.bytes;
+        }
+    }
+
+    let mut usages: Vec<_> = usages.into_values().collect();
+    usages.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.version.cmp(&b.version))
+    });
+    let total: u64 = usages.iter().map(|usage| usage.bytes).sum();
+    let count = usages.len();
+    usages.truncate(options.top.unwrap_or(count));
+
+    let units = options.options.units;
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL_CONDENSED);
+    table.set_header(["Crate", "Version", "Projects", "Size"]);
+    for usage in &usages {
+        table.add_row([
+            usage.name.clone(),
+            usage.version.clone(),
+            usage.projects.len().to_string(),
+            Size::with_units(usage.bytes, units).to_string(),
+        ]);
+    }
+    table.add_row([
+        format!("Total ({count} crates)"),
+        String::new(),
+        format!("{} projects", projects.len()),
+        Size::with_units(total, units).to_string(),
+    ]);
+    table.to_string()
+}
+
+/// Collects the compiled crates in the `deps` and `.fingerprint` directories of a target directory,
+/// keyed by their crate name (with underscores) and metadata hash
+fn compiled_units(target_dir: &Path, size_mode: SizeMode) -> HashMap<(String, String), Unit> {
+    let list = utility::list_files(target_dir);
+    let mut units: HashMap<(String, String), Unit> = HashMap::new();
+
+    for file in &list.files {
+        let Some(parent) = file.path.parent() else {
+            continue;
+        };
+        let parent_name = parent.file_name().unwrap_or_default().to_string_lossy();
+        let key = if parent_name == "deps" {
+            unit_key(
+                &file.path.file_name().unwrap_or_default().to_string_lossy(),
+                true,
+            )
+        } else if parent.parent().and_then(Path::file_name) == Some(".fingerprint".as_ref()) {
+            unit_key(&parent_name, false)
+        } else {
+            None
+        };
+        let Some(key) = key else {
+            continue;
+        };
+
+        let unit = units.entry(key).or_default();
+        unit.bytes += match size_mode {
+            SizeMode::Apparent => file.bytes,
+            SizeMode::Disk => file.disk_bytes,
+        };
+        if unit.version.is_none()
+            && file.path.extension().is_some_and(|ext| ext == "d")
+            && let Ok(dep_info) = fs::read_to_string(&file.path)
+        {
+            if let Some((package, version)) = source_version(&dep_info) {
+                unit.package = Some(package);
+                unit.version = Some(version);
+                unit.local = false;
+            } else {
+                unit.local = true;
+            }
+        }
+    }
+
+    // Fingerprint directories can use another hash than the files in `deps`,
+    // so take the version from the other builds of the crate if it is unambiguous
+    let mut sources: HashMap<String, HashSet<(String, String)>> = HashMap::new();
+    let mut local = HashSet::new();
+    for ((name, _), unit) in &units {
+        if let (Some(package), Some(version)) = (&unit.package, &unit.version) {
+            sources
+                .entry(name.clone())
+                .or_default()
+                .insert((package.clone(), version.clone()));
+        } else if unit.local {
+            local.insert(name.clone());
+        }
+    }
+    for ((name, _), unit) in &mut units {
+        if unit.version.is_none()
+            && let Some(found) = sources.get(name)
+            && let [(package, version)] = found.iter().collect::<Vec<_>>().as_slice()
+        {
+            unit.package = Some(package.clone());
+            unit.version = Some(version.clone());
+        } else if unit.version.is_none() && !sources.contains_key(name) && local.contains(name) {
+            unit.local = true;
+        }
+    }
+
+    units
+}
+
+/// Splits a file name in `deps` like `libserde-1a2b3c.rlib`, or a directory name
+/// in `.fingerprint` like `serde-1a2b3c`, into the crate name and metadata hash
+fn unit_key(name: &str, is_file: bool) -> Option<(String, String)> {
+    let (stem, extension) = if is_file {
+        name.split_once('.').unwrap_or((name, ""))
+    } else {
+        (name, "")
+    };
+    let (crate_name, hash) = stem.rsplit_once('-')?;
+    if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    // Libraries get a `lib` prefix, binaries and dep-info files do not
+    let crate_name = match extension {
+        "rlib" | "rmeta" | "so" | "dylib" | "a" => {
+            crate_name.strip_prefix("lib").unwrap_or(crate_name)
+        }
+        _ => crate_name,
+    };
+    Some((crate_name.replace('-', "_"), hash.to_owned()))
+}
+
+/// Reads the package name and version from the registry sources listed in the content
+/// of a dep-info file, crates from git checkouts get `git` as version
+fn source_version(dep_info: &str) -> Option<(String, String)> {
+    dep_info.split_whitespace().find_map(|path| {
+        let path = path.replace('\\', "/");
+        if let Some((_, rest)) = path.split_once("/registry/src/") {
+            // Skip the directory of the registry index
+            let dir = rest.split('/').nth(1)?;
+            let (name, version) = split_version(dir)?;
+            Some((name.to_owned(), version.to_owned()))
+        } else if let Some((_, rest)) = path.split_once("/git/checkouts/") {
+            let (name, _) = rest.split('/').next()?.rsplit_once('-')?;
+            Some((name.to_owned(), "git".to_owned()))
+        } else {
+            None
+        }
+    })
+}
+
+/// Splits a registry directory name like `proc-macro2-1.0.80` into the package name and version
+fn split_version(dir: &str) -> Option<(&str, &str)> {
+    let is_number = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+    dir.match_indices('-')
+        .map(|(position, _)| (&dir[..position], &dir[position + 1..]))
+        .find(|(_, version)| {
+            let mut parts = version.splitn(3, '.');
+            parts.next().is_some_and(is_number)
+                && parts.next().is_some_and(is_number)
+                && parts
+                    .next()
+                    .is_some_and(|patch| patch.starts_with(|c: char| c.is_ascii_digit()))
+        })
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use comfy_table::{Table, presets::UTF8_FULL_CONDENSED};
+
+use crate::{
+    cli::opts::{self, StatsOptions},
+    commands::{percentage, total_size_of_projects},
+    data::{Project, Size, Units},
+    filter::ProjectFilter,
+    history::{Snapshot, SnapshotProject},
+    utility,
+};
+
+/// The projects the current scan could have found. Projects of the snapshot outside
+/// of it, below other roots or excluded by a filter, are left out of the comparison.
+pub struct Scope {
+    /// Canonical search roots
+    roots: Vec<PathBuf>,
+    filter: ProjectFilter,
+}
+
+impl Scope {
+    pub fn new(roots: &[PathBuf], filter: ProjectFilter) -> Self {
+        Self {
+            roots: roots
+                .iter()
+                .map(|root| fs::canonicalize(root).unwrap_or_else(|_| root.clone()))
+                .collect(),
+            filter,
+        }
+    }
+
+    /// Returns the scope of a scan with the options, below the roots registered in `config_path`
+    /// if no path is given. With `--projects-from` only the listed projects that were found
+    /// are in scope, as the list may have been read from stdin.
+    pub fn of(
+        projects: &[Project],
+        options: &StatsOptions,
+        config_path: &Path,
+    ) -> anyhow::Result<Self> {
+        let options = &options.options;
+        let roots = if options.projects_from.is_some() {
+            projects
+                .iter()
+                .map(|project| project.path.clone())
+                .collect()
+        } else {
+            opts::scan_roots(&options.path, config_path)?
+                .iter()
+                .map(utility::sanitize_path_input)
+                .collect::<anyhow::Result<Vec<_>>>()?
+        };
+        let filter =
+            ProjectFilter::new(&options.include, &options.exclude, &options.exclude_regex)?;
+        Ok(Self::new(&roots, filter))
+    }
+
+    /// Returns `true` if a project of the snapshot that is missing from the scan was removed.
+    /// A project that still exists was left out by `--max-depth`, `--stale` or another
+    /// filter that cannot be checked against the snapshot.
+    fn removed(&self, project: &SnapshotProject) -> bool {
+        !project.path.join("Cargo.toml").exists()
+            && self.roots.iter().any(|root| {
+                project.path.starts_with(root)
+                    && utility::project_id(&project.path, root)
+                        .is_ok_and(|id| self.filter.is_match(&project.name, &id))
+            })
+    }
+}
+
+/// A project in the current scan, the snapshot, or both
+struct Change {
+    name: String,
+    id: String,
+    before: Option<u64>,
+    after: Option<u64>,
+}
+
+impl Change {
+    fn growth(&self) -> i128 {
+        i128::from(self.after.unwrap_or(0)) - i128::from(self.before.unwrap_or(0))
+    }
+}
+
+/// Renders the growth of every project since the snapshot, largest growth first,
+/// including projects that are new or were removed since then.
+/// Projects of the snapshot outside of `scope` are neither listed nor part of the total.
+pub fn render(
+    projects: &[Project],
+    snapshot: &Snapshot,
+    scope: &Scope,
+    options: &StatsOptions,
+) -> String {
+    let units = options.options.units;
+    let scanned: HashMap<PathBuf, &Project> = projects
+        .iter()
+        .map(|project| {
+            let path = fs::canonicalize(&project.path).unwrap_or_else(|_| project.path.clone());
+            (path, project)
+        })
+        .collect();
+    let before: Vec<_> = snapshot
+        .projects
+        .iter()
+        .filter(|project| scanned.contains_key(&project.path) || scope.removed(project))
+        .collect();
+    // The recorded total counts shared hard links once, but covers the whole snapshot
+    let before_total = if before.len() == snapshot.projects.len() {
+        snapshot.total
+    } else {
+        before.iter().map(|project| project.size).sum()
+    };
+
+    let mut changes: HashMap<PathBuf, Change> = before
+        .into_iter()
+        .map(|project| {
+            let change = Change {
+                name: project.name.clone(),
+                id: project.id.clone(),
+                before: Some(project.size),
+                after: None,
+            };
+            (project.path.clone(), change)
+        })
+        .collect();
+    for (path, project) in scanned {
+        let change = changes.entry(path).or_insert_with(|| Change {
+            name: project.name.clone(),
+            id: project.id.clone(),
+            before: None,
+            after: None,
+        });
+        change.name.clone_from(&project.name);
+        change.id.clone_from(&project.id);
+        change.after = Some(project.size);
+    }
+
+    let mut changes: Vec<_> = changes.into_values().collect();
+    changes.sort_by(|a, b| {
+        b.growth()
+            .cmp(&a.growth())
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL_CONDENSED);
+    table.set_header(["Project", "Id", "Before", "After", "Growth", "Change"]);
+    let size = |bytes: Option<u64>| {
+        bytes.map_or_else(
+            || "-".to_owned(),
+            |b| Size::with_units(b, units).to_string(),
+        )
+    };
+    for change in &changes {
+        let share = match (change.before, change.after) {
+            (None, _) => "new".to_owned(),
+            (_, None) => "removed".to_owned(),
+            (Some(before), Some(after)) => relative(before, after),
+        };
+        table.add_row([
+            change.name.clone(),
+            change.id.clone(),
+            size(change.before),
+            size(change.after),
+            growth(change.growth(), units),
+            share,
+        ]);
+    }
+
+    let total = total_size_of_projects(projects);
+    table.add_row([
+        format!("Total ({} projects)", projects.len()),
+        String::new(),
+        size(Some(before_total)),
+        size(Some(total)),
+        growth(i128::from(total) - i128::from(before_total), units),
+        relative(before_total, total),
+    ]);
+    table.to_string()
+}
+
+/// Formats a change in bytes with its sign
+fn growth(bytes: i128, units: Units) -> String {
+    let size = Size::with_units(
+        u64::try_from(bytes.unsigned_abs()).unwrap_or(u64::MAX),
+        units,
+    );
+    match bytes {
+        0 => size.to_string(),
+        1.. => format!("+{size}"),
+        _ => format!("-{size}"),
+    }
+}
+
+/// Formats the change between two sizes in percent
+fn relative(before: u64, after: u64) -> String {
+    if before == 0 {
+        return if after == 0 { "0.0%" } else { "new" }.to_owned();
+    }
+    if after >= before {
+        format!("+{:.1}%", percentage(after - before, before))
+    } else {
+        format!("-{:.1}%", percentage(before - after, before))
+    }
+}
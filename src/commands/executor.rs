@@ -10,18 +10,8 @@ pub fn run(projects: &[Project], options: &Options, command: &str) -> anyhow::Re
     let start_time = std::time::Instant::now();
     let processed_projects: Arc<RwLock<Vec<Project>>> = Arc::new(RwLock::new(vec![]));
     let failed_projects = Arc::new(RwLock::new(vec![]));
-    // filter excluded projects
     let mut projects_to_execute = projects.to_vec();
 
-    if let Some(excluded_projects) = options.exclude.as_ref() {
-        log::debug!("Excluding folders: {excluded_projects}");
-        excluded_projects
-            .split(',')
-            .for_each(|ex| projects_to_execute.retain(|project| project.name != ex.trim()));
-    } else {
-        log::debug!("No folder excluded");
-    }
-
     if options.parallel {
         log::warn!(
             "EXPERIMENTAL: Executing 'cargo {command}' on {} projects in parallel mode",
@@ -29,7 +19,7 @@ pub fn run(projects: &[Project], options: &Options, command: &str) -> anyhow::Re
         );
     }
 
-    projects_to_execute.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    projects_to_execute.sort_by_key(|project| (project.name.to_lowercase(), project.id.clone()));
 
     // Create progress bar
     let pb = ProgressBar::new(projects_to_execute.len() as u64);
@@ -44,8 +34,7 @@ pub fn run(projects: &[Project], options: &Options, command: &str) -> anyhow::Re
         let mut args = options.args.clone();
         if command == "build" || command == "check" || command == "doc" || command == "test" {
             let nproc = thread::available_parallelism()
-                .map(std::num::NonZero::get)
-                .unwrap_or(2)
+                .map_or(2, std::num::NonZero::get)
                 .max(2) // ensure at least 2 before dividing
                 .div(3)
                 .max(2);
@@ -99,7 +88,7 @@ pub fn run(projects: &[Project], options: &Options, command: &str) -> anyhow::Re
             failed_projects
                 .read()
                 .iter()
-                .map(|p| p.name.clone())
+                .map(|p| p.id.clone())
                 .collect::<Vec<_>>()
                 .join(", ")
         )
@@ -135,7 +124,7 @@ fn execute(
     log::debug!(
         "Running `cargo {command} {}` for project: {:?}",
         args.join(" "),
-        project.name
+        project.id
     );
 
     let mut cmd = Command::new("cargo");
@@ -157,7 +146,7 @@ fn execute(
             processed_projects.write().push(project.clone());
             log::debug!(
                 "Successfully ran `cargo {command}` on {} ({i}/{})",
-                project.name,
+                project.id,
                 projects_to_execute.len()
             );
         }
@@ -165,13 +154,13 @@ fn execute(
             failed_projects.write().push(project.clone());
             log::debug!(
                 "Failed to run `cargo {command}` on {} with exit code {}",
-                project.name,
+                project.id,
                 output.status
             );
         }
         Err(e) => {
             failed_projects.write().push(project.clone());
-            log::debug!("Failed to execute the command on {}: {e}", project.name);
+            log::debug!("Failed to execute the command on {}: {e}", project.id);
         }
     }
 }
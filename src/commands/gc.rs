@@ -0,0 +1,288 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use comfy_table::{Table, presets::UTF8_FULL_CONDENSED};
+
+use crate::{
+    cli::opts::HomeOptions,
+    commands::home::{self, Removal},
+    data::{Size, SizeMode},
+    sqlite::Database,
+    utility,
+};
+
+/// Name of the database in the cargo home where cargo tracks when downloads were last used
+const TRACKER_FILE: &str = ".global-cache";
+
+/// When the downloaded crates and extracted sources were last used, from the tracker of cargo
+struct Tracker {
+    /// Keyed by the registry directory and the `.crate` file name
+    crates: HashMap<(String, String), SystemTime>,
+    /// Keyed by the registry directory and the source directory name
+    sources: HashMap<(String, String), SystemTime>,
+}
+
+impl Tracker {
+    /// Reads the tracker of the cargo home, `None` if the file times have to be used instead
+    fn find(cargo_home: &Path) -> Option<Self> {
+        let path = cargo_home.join(TRACKER_FILE);
+        if !path.exists() {
+            log::info!(
+                "Cargo does not track the last use of downloads yet, \
+                 using file access and modification times instead"
+            );
+            return None;
+        }
+        // Changes in a rollback journal or write-ahead log are not read, the timestamps may be stale
+        for suffix in ["-journal", "-wal"] {
+            let journal = cargo_home.join(format!("{TRACKER_FILE}{suffix}"));
+            if fs::metadata(&journal).is_ok_and(|metadata| metadata.len() > 0) {
+                log::info!(
+                    "{} is being written, using file access and modification times instead",
+                    journal.display()
+                );
+                return None;
+            }
+        }
+        Self::read(&path)
+            .inspect_err(|error| {
+                log::warn!("{error:#}, using file access and modification times instead");
+            })
+            .ok()
+    }
+
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let database = Database::open(path)?;
+        // `registry_index.id` is the rowid
+        let registries: HashMap<i64, String> = database
+            .rows("registry_index")?
+            .into_iter()
+            .filter_map(|row| Some((row.rowid, row.get(1).as_text()?.to_owned())))
+            .collect();
+
+        // Both tables have the columns registry_id, name, size and timestamp
+        let last_use = |table: &str| -> anyhow::Result<HashMap<(String, String), SystemTime>> {
+            Ok(database
+                .rows(table)?
+                .iter()
+                .filter_map(|row| {
+                    let registry = registries.get(&row.get(0).as_integer()?)?;
+                    let name = row.get(1).as_text()?;
+                    let timestamp = u64::try_from(row.get(3).as_integer()?).ok()?;
+                    Some((
+                        (registry.clone(), name.to_owned()),
+                        SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp),
+                    ))
+                })
+                .collect())
+        };
+        Ok(Self {
+            crates: last_use("registry_crate")?,
+            sources: last_use("registry_src")?,
+        })
+    }
+}
+
+/// The unused downloads of a registry
+#[derive(Default)]
+struct Unused {
+    crates: Vec<Removal>,
+    sources: Vec<Removal>,
+}
+
+impl Unused {
+    fn removals(&self) -> impl Iterator<Item = &Removal> {
+        self.crates.iter().chain(&self.sources)
+    }
+
+    fn size(&self) -> u64 {
+        self.removals().map(|removal| removal.size).sum()
+    }
+
+    /// Keeps only the downloads that are still unused in `now`
+    fn retain_unused(&mut self, now: Option<&Self>) {
+        let paths: HashSet<&Path> = now.map_or_else(HashSet::new, |now| {
+            now.removals()
+                .map(|removal| removal.path.as_path())
+                .collect()
+        });
+        self.crates
+            .retain(|removal| paths.contains(removal.path.as_path()));
+        self.sources
+            .retain(|removal| paths.contains(removal.path.as_path()));
+    }
+}
+
+/// Removes the `.crate` files and extracted sources that were not used for `max_age`
+#[expect(clippy::print_stdout, reason = "No other way to show the summary")]
+pub fn run(options: &HomeOptions, max_age: Duration, cargo_home: &Path) -> anyhow::Result<()> {
+    let cutoff = SystemTime::now()
+        .checked_sub(max_age)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut unused = unused(
+        cargo_home,
+        Tracker::find(cargo_home).as_ref(),
+        cutoff,
+        options.size_mode,
+    );
+    let count = |unused: &BTreeMap<String, Unused>| {
+        unused.values().map(|u| u.removals().count()).sum::<usize>()
+    };
+    if count(&unused) == 0 {
+        log::info!(
+            "Nothing was unused for {}",
+            utility::format_duration(max_age)
+        );
+        return Ok(());
+    }
+
+    let summary = |unused: &BTreeMap<String, Unused>, freed: &BTreeMap<&String, u64>, header| {
+        let mut table = Table::new();
+        table.load_preset(UTF8_FULL_CONDENSED);
+        table.set_header(["Registry", "Crates", "Sources", header]);
+        for (registry, entries) in unused {
+            table.add_row([
+                registry.clone(),
+                entries.crates.len().to_string(),
+                entries.sources.len().to_string(),
+                Size::with_units(freed.get(registry).copied().unwrap_or(0), options.units)
+                    .to_string(),
+            ]);
+        }
+        table.add_row([
+            "Total".to_owned(),
+            unused
+                .values()
+                .map(|u| u.crates.len())
+                .sum::<usize>()
+                .to_string(),
+            unused
+                .values()
+                .map(|u| u.sources.len())
+                .sum::<usize>()
+                .to_string(),
+            Size::with_units(freed.values().sum(), options.units).to_string(),
+        ]);
+        table.to_string()
+    };
+
+    let size: BTreeMap<&String, u64> = unused
+        .iter()
+        .map(|(registry, entries)| (registry, entries.size()))
+        .collect();
+    if options.dry_run {
+        log::info!(
+            "Would remove the downloads that were not used for {}:",
+            utility::format_duration(max_age)
+        );
+        println!("{}", summary(&unused, &size, "Would free"));
+        return Ok(());
+    }
+    let removals = count(&unused);
+    let question = format!(
+        "Remove {removals} downloads that were not used for {} and free {}?",
+        utility::format_duration(max_age),
+        Size::with_units(size.values().sum(), options.units)
+    );
+    if !utility::confirm(&question, options.yes)? {
+        log::info!("Nothing removed");
+        return Ok(());
+    }
+    let _lock = home::lock_package_cache(cargo_home)?;
+    // A build may have used some of the downloads while the question was asked
+    let now = self::unused(
+        cargo_home,
+        Tracker::find(cargo_home).as_ref(),
+        cutoff,
+        options.size_mode,
+    );
+    for (registry, entries) in &mut unused {
+        entries.retain_unused(now.get(registry));
+    }
+    if count(&unused) < removals {
+        log::info!(
+            "Keeping {} downloads that were used in the meantime",
+            removals - count(&unused)
+        );
+    }
+
+    let mut failed = 0;
+    let mut freed = BTreeMap::new();
+    for (registry, entries) in &unused {
+        let (crates, crate_errors) = home::remove(&entries.crates);
+        let (sources, source_errors) = home::remove(&entries.sources);
+        freed.insert(registry, crates + sources);
+        failed += crate_errors + source_errors;
+    }
+    println!("{}", summary(&unused, &freed, "Freed"));
+    anyhow::ensure!(failed == 0, "Some entries ({failed}) could not be removed");
+    Ok(())
+}
+
+/// Collects the downloads of every registry that were last used before `cutoff`.
+/// Downloads that the tracker does not know are judged by their file times.
+fn unused(
+    cargo_home: &Path,
+    tracker: Option<&Tracker>,
+    cutoff: SystemTime,
+    size_mode: SizeMode,
+) -> BTreeMap<String, Unused> {
+    let mut unused: BTreeMap<String, Unused> = BTreeMap::new();
+    let cache_dir = cargo_home.join("registry/cache");
+    for registry in utility::subdirs(&cache_dir) {
+        for (file, modified) in home::crate_files(&cache_dir.join(&registry)) {
+            let name = file.path.file_name().unwrap_or_default().to_string_lossy();
+            let key = (registry.clone(), name.to_string());
+            let last_use = tracker
+                .and_then(|tracker| tracker.crates.get(&key).copied())
+                .or_else(|| file_time(&file.path).max(modified));
+            if last_use.is_some_and(|time| time >= cutoff) {
+                continue;
+            }
+            unused
+                .entry(registry.clone())
+                .or_default()
+                .crates
+                .push(Removal {
+                    size: match size_mode {
+                        SizeMode::Apparent => file.bytes,
+                        SizeMode::Disk => file.disk_bytes,
+                    },
+                    path: file.path,
+                });
+        }
+    }
+
+    let src_dir = cargo_home.join("registry/src");
+    for registry in utility::subdirs(&src_dir) {
+        for name in utility::subdirs(&src_dir.join(&registry)) {
+            let path = src_dir.join(&registry).join(&name);
+            let key = (registry.clone(), name);
+            let last_use = tracker
+                .and_then(|tracker| tracker.sources.get(&key).copied())
+                .or_else(|| file_time(&path));
+            if last_use.is_some_and(|time| time >= cutoff) {
+                continue;
+            }
+            unused
+                .entry(registry.clone())
+                .or_default()
+                .sources
+                .push(Removal {
+                    size: utility::get_folder_size(&path).get(size_mode),
+                    path,
+                });
+        }
+    }
+    unused
+}
+
+/// Returns the later of the access and modification time of a file or directory
+fn file_time(path: &Path) -> Option<SystemTime> {
+    let metadata = fs::metadata(path).ok()?;
+    metadata.accessed().ok().max(metadata.modified().ok())
+}
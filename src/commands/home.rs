@@ -0,0 +1,256 @@
+use std::{
+    fs::{self, File, OpenOptions, TryLockError},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::Context as _;
+use comfy_table::{Table, presets::UTF8_FULL_CONDENSED};
+use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
+
+use crate::{
+    cli::opts::{HomeCommand, HomeOptions},
+    commands::{bins, gc, percentage},
+    data::{FileEntry, Size, SizeMode},
+    utility,
+};
+
+/// The directories of the cargo home that hold downloaded dependencies, in display order
+const LOCATIONS: [&str; 5] = [
+    "registry/index",
+    "registry/cache",
+    "registry/src",
+    "git/db",
+    "git/checkouts",
+];
+
+/// Files cargo locks while it downloads and extracts packages into the cargo home
+const PACKAGE_CACHE_LOCKS: [&str; 2] = [".package-cache", ".package-cache-mutate"];
+
+/// A registry or git repository in one of the `LOCATIONS`
+struct Entry {
+    location: &'static str,
+    name: String,
+    path: PathBuf,
+    /// Number of crates or checked out revisions, if the location holds several per entry
+    count: Option<usize>,
+    size: u64,
+}
+
+/// A file or directory to remove
+pub struct Removal {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+#[expect(clippy::print_stdout, reason = "No other way to show the sizes")]
+pub fn run(options: &HomeOptions, cargo_home: &Path) -> anyhow::Result<()> {
+    log::info!("Cargo home: {}", cargo_home.display());
+    match &options.command {
+        Some(HomeCommand::Gc { max_age }) => return gc::run(options, *max_age, cargo_home),
+        Some(HomeCommand::Bins {
+            prune_unused,
+            trust_atime,
+        }) => {
+            return bins::run(options, *prune_unused, *trust_atime, cargo_home);
+        }
+        None => {}
+    }
+
+    let entries = entries(cargo_home, options.size_mode);
+    println!("{}", render(&entries, options));
+
+    if !options.clean_src && !options.clean_checkouts && !options.clean_cache {
+        return Ok(());
+    }
+    let removals = removals(&entries, options, SystemTime::now());
+    let size: u64 = removals.iter().map(|removal| removal.size).sum();
+    let size = Size::with_units(size, options.units);
+    if removals.is_empty() {
+        log::info!("Nothing to remove");
+        return Ok(());
+    }
+    if options.dry_run {
+        for removal in &removals {
+            log::info!("Would remove {}", removal.path.display());
+        }
+        log::info!("Would free {size} ({} entries)", removals.len());
+        return Ok(());
+    }
+    if !utility::confirm(
+        &format!("Remove {} entries and free {size}?", removals.len()),
+        options.yes,
+    )? {
+        log::info!("Nothing removed");
+        return Ok(());
+    }
+
+    let _lock = lock_package_cache(cargo_home)?;
+    let (freed, failed) = remove(&removals);
+    log::info!("Freed {}", Size::with_units(freed, options.units));
+    anyhow::ensure!(failed == 0, "Some entries ({failed}) could not be removed");
+    Ok(())
+}
+
+/// Collects the registries and git repositories in every location of the cargo home
+fn entries(cargo_home: &Path, size_mode: SizeMode) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = LOCATIONS
+        .into_par_iter()
+        .flat_map_iter(|location| {
+            let dir = cargo_home.join(location);
+            utility::subdirs(&dir).into_iter().map(move |name| {
+                let path = dir.join(&name);
+                let count = match location {
+                    "registry/cache" => Some(crate_files(&path).len()),
+                    "registry/src" | "git/checkouts" => Some(utility::subdirs(&path).len()),
+                    _ => None,
+                };
+                Entry {
+                    location,
+                    name,
+                    size: utility::get_folder_size(&path).get(size_mode),
+                    path,
+                    count,
+                }
+            })
+        })
+        .collect();
+
+    let order = |location: &str| LOCATIONS.iter().position(|l| *l == location);
+    entries.sort_by(|a, b| {
+        order(a.location)
+            .cmp(&order(b.location))
+            .then_with(|| b.size.cmp(&a.size))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    entries
+}
+
+/// Renders the size of every registry and git repository, with its share of the total
+fn render(entries: &[Entry], options: &HomeOptions) -> String {
+    let total: u64 = entries.iter().map(|entry| entry.size).sum();
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL_CONDENSED);
+    table.set_header(["Location", "Name", "Items", "Size", "Share"]);
+    for entry in entries {
+        table.add_row([
+            entry.location.to_owned(),
+            entry.name.clone(),
+            entry
+                .count
+                .map_or_else(|| "-".to_owned(), |count| count.to_string()),
+            Size::with_units(entry.size, options.units).to_string(),
+            format!("{:.1}%", percentage(entry.size, total)),
+        ]);
+    }
+    table.add_row([
+        "Total".to_owned(),
+        String::new(),
+        String::new(),
+        Size::with_units(total, options.units).to_string(),
+        String::new(),
+    ]);
+    table.to_string()
+}
+
+/// Returns the entries selected by the clean options
+fn removals(entries: &[Entry], options: &HomeOptions, now: SystemTime) -> Vec<Removal> {
+    let mut removals = vec![];
+    for entry in entries {
+        match entry.location {
+            "registry/src" if options.clean_src => {}
+            "git/checkouts" if options.clean_checkouts => {}
+            "registry/cache" if options.clean_cache => {
+                // Only files that are old enough, the registry directory itself stays
+                removals.extend(
+                    crate_files(&entry.path)
+                        .into_iter()
+                        .filter(|(_, modified)| {
+                            options.older_than.is_none_or(|age| {
+                                modified.is_some_and(|modified| {
+                                    now.duration_since(modified).is_ok_and(|d| d >= age)
+                                })
+                            })
+                        })
+                        .map(|(file, _)| Removal {
+                            size: match options.size_mode {
+                                SizeMode::Apparent => file.bytes,
+                                SizeMode::Disk => file.disk_bytes,
+                            },
+                            path: file.path,
+                        }),
+                );
+                continue;
+            }
+            _ => continue,
+        }
+        removals.push(Removal {
+            path: entry.path.clone(),
+            size: entry.size,
+        });
+    }
+    removals
+}
+
+/// Lists the `.crate` files in a registry cache with their modification time
+pub fn crate_files(dir: &Path) -> Vec<(FileEntry, Option<SystemTime>)> {
+    utility::list_files(dir)
+        .files
+        .into_iter()
+        .filter(|file| file.path.extension().is_some_and(|ext| ext == "crate"))
+        .map(|file| {
+            let modified = fs::metadata(&file.path).and_then(|m| m.modified()).ok();
+            (file, modified)
+        })
+        .collect()
+}
+
+/// Removes the files and directories, returning the freed bytes and the number of failures.
+/// Failures are reported and the remaining entries are still removed.
+pub fn remove(removals: &[Removal]) -> (u64, usize) {
+    let mut freed = 0;
+    let mut failed = 0;
+    for removal in removals {
+        let result = if removal.path.is_dir() {
+            fs::remove_dir_all(&removal.path)
+        } else {
+            fs::remove_file(&removal.path)
+        };
+        match result {
+            Ok(()) => freed += removal.size,
+            Err(error) => {
+                log::error!("Failed to remove {}: {error}", removal.path.display());
+                failed += 1;
+            }
+        }
+    }
+    (freed, failed)
+}
+
+/// Takes the locks cargo holds while it downloads or extracts packages, so nothing is removed
+/// that a running build is using. Fails if cargo holds them, they are released on drop.
+pub fn lock_package_cache(cargo_home: &Path) -> anyhow::Result<Vec<File>> {
+    PACKAGE_CACHE_LOCKS
+        .iter()
+        .map(|name| {
+            let path = cargo_home.join(name);
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)
+                .context(format!("Failed to open {}", path.display()))?;
+            match file.try_lock() {
+                Ok(()) => Ok(file),
+                Err(TryLockError::WouldBlock) => anyhow::bail!(
+                    "Cargo is using the package cache ({} is locked), try again once it finished",
+                    path.display()
+                ),
+                Err(TryLockError::Error(error)) => {
+                    Err(error).context(format!("Failed to lock {}", path.display()))
+                }
+            }
+        })
+        .collect()
+}
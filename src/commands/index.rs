@@ -0,0 +1,19 @@
+use crate::{
+    cli::opts::{Options, OptionsTrait as _},
+    commands::total_size_of_projects,
+    data::Size,
+    index::Index,
+    utility::UserDirs,
+};
+
+pub fn run(options: &Options, dirs: &UserDirs) -> anyhow::Result<()> {
+    let projects = options.scan(dirs, true)?;
+
+    log::info!(
+        "Indexed {} projects ({}) into {}",
+        projects.len(),
+        Size::with_units(total_size_of_projects(&projects), options.units),
+        Index::path(dirs).display()
+    );
+    Ok(())
+}
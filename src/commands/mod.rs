@@ -1,29 +1,57 @@
 use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
 
-use crate::data::{Project, Size};
+use crate::data::{self, Project, Size, SizeMode, Units};
 
+pub mod bins;
+pub mod breakdown;
 pub mod clean;
+pub mod crates;
+pub mod diff;
 pub mod executor;
+pub mod gc;
+pub mod home;
+pub mod index;
+pub mod roots;
 pub mod stats;
+pub mod toolchains;
+pub mod tree;
 
+/// Returns the total size of the projects, counting files hard-linked between them once
 pub fn total_size_of_projects(projects: &[Project]) -> u64 {
-    projects
-        .par_iter()
-        .map(|project| project.size.size_in_bytes())
-        .sum()
+    let sum: u64 = projects.par_iter().map(|project| project.size).sum();
+    sum - data::duplicate_link_size(projects)
 }
 
-pub fn print_status(projects: &[Project], cleaned: &[Project], exclude: Option<&String>) {
-    let total_size = total_size_of_projects(cleaned);
+/// Returns the share of `part` in `total` in percent
+#[expect(clippy::cast_precision_loss, reason = "Only used for display")]
+pub fn percentage(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64 * 100.0
+    }
+}
+
+/// Logs the space freed by cleaning, on disk like `df` reports it and the apparent size.
+/// Hard links between projects are only known in the size mode of the scan,
+/// they are counted once in that size only.
+pub fn print_status(projects: &[Project], cleaned: &[Project], size_mode: SizeMode, units: Units) {
+    let shared = data::duplicate_link_size(cleaned);
+    let (disk_shared, apparent_shared) = match size_mode {
+        SizeMode::Disk => (shared, 0),
+        SizeMode::Apparent => (0, shared),
+    };
+    let disk: u64 = cleaned.iter().map(|p| p.disk_size).sum::<u64>() - disk_shared;
+    let apparent: u64 = cleaned.iter().map(|p| p.apparent_size).sum::<u64>() - apparent_shared;
 
-    let skipped = exclude.map_or_else(String::new, |skip| format!("\n(Skipped: {skip})"));
     log::info!(
-        "Cleaned {} ({} Projects)\nProjects: {}{skipped}",
-        Size::to_size(total_size),
+        "Cleaned {} on disk, {} apparent ({} Projects)\nProjects: {}",
+        Size::with_units(disk, units),
+        Size::with_units(apparent, units),
         projects.len(),
         cleaned
             .iter()
-            .map(|p| p.name.clone())
+            .map(|p| p.id.clone())
             .collect::<Vec<String>>()
             .join(", ")
     );
@@ -0,0 +1,55 @@
+use std::{fs, path::Path};
+
+use anyhow::Context as _;
+
+use crate::{cli::RootsCommand, config::Config};
+
+pub fn run(command: &RootsCommand, config_path: &Path) -> anyhow::Result<()> {
+    let mut config = Config::load(config_path)?;
+
+    match command {
+        RootsCommand::Add { path } => {
+            let root = fs::canonicalize(path)
+                .context(format!("Failed to resolve path: {}", path.display()))?;
+            anyhow::ensure!(root.is_dir(), "The provided path is not a directory.");
+
+            if config.roots.contains(&root) {
+                log::info!("Root already registered: {}", root.display());
+            } else {
+                log::info!("Added root: {}", root.display());
+                config.roots.push(root);
+                config.save(config_path)?;
+            }
+        }
+        RootsCommand::Remove { path } => {
+            let root = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+            let count = config.roots.len();
+            config.roots.retain(|r| r != &root && r != path);
+
+            anyhow::ensure!(
+                config.roots.len() < count,
+                "Root is not registered: {}",
+                path.display()
+            );
+            log::info!("Removed root: {}", root.display());
+            config.save(config_path)?;
+        }
+        RootsCommand::List => list(&config),
+    }
+    Ok(())
+}
+
+#[expect(clippy::print_stdout, reason = "No other way to show the roots")]
+fn list(config: &Config) {
+    if config.roots.is_empty() {
+        log::info!("No roots registered, the current directory is used by default.");
+        return;
+    }
+    for root in &config.roots {
+        if root.is_dir() {
+            println!("{}", root.display());
+        } else {
+            println!("{} (missing)", root.display());
+        }
+    }
+}
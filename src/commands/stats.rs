@@ -1,30 +1,480 @@
+use core::cmp::Reverse;
+use std::time::SystemTime;
+
+use anyhow::Context as _;
 use comfy_table::{Table, presets::UTF8_FULL_CONDENSED};
+use rayon::iter::{IntoParallelRefMutIterator as _, ParallelIterator as _};
+use serde::Serialize;
 
 use crate::{
-    commands::total_size_of_projects,
-    data::{Project, Size},
+    cli::opts::{StatsFormat, StatsOptions, StatsSort},
+    commands::{
+        breakdown, crates,
+        diff::{self, Scope},
+        percentage, total_size_of_projects, tree,
+    },
+    config::Config,
+    data::{self, Project, Size, Units},
+    git::{self, GitStatus},
+    history::{self, Snapshot},
+    toolchain::Toolchain,
+    utility::{self, UserDirs},
 };
 
+/// The document written by `stats --format json`, all sizes are in bytes
+#[derive(Serialize)]
+struct Report<'a> {
+    projects: Vec<&'a Project>,
+    /// Sums over the shown projects, only present if `--top` hides some projects
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shown: Option<Total>,
+    total: Total,
+}
+
+/// Sums over all projects, files hard-linked between projects are counted once in `size`
+#[derive(Serialize)]
+struct Total {
+    projects: usize,
+    size: u64,
+    apparent_size: u64,
+    disk_size: u64,
+    hardlinked: u64,
+    shared: u64,
+    size_errors: usize,
+}
+
+/// Shows the stats, and records them in the size history in `dirs` if requested
+pub fn show(projects: &[Project], options: &StatsOptions, dirs: &UserDirs) -> anyhow::Result<()> {
+    let mut projects = projects.to_vec();
+    if options.long {
+        projects.par_iter_mut().for_each(|project| {
+            project.toolchain = Some(Toolchain::read(&project.path));
+        });
+    }
+    if options.git {
+        git::read_all(&mut projects);
+    }
+    let projects = projects.as_slice();
+    print(projects, options, dirs)?;
+
+    if options.record {
+        let history_path = &history::path(dirs);
+        let snapshot = Snapshot::new(projects, options.options.size_mode, SystemTime::now());
+        history::append(history_path, &snapshot)?;
+        log::info!(
+            "Recorded the sizes of {} projects in {}",
+            projects.len(),
+            history_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Finds the project with the given id, or the only project with the given name.
+/// An id is unique, a name can be shared by projects below different directories.
+pub fn select<'a>(projects: &'a [Project], name: &str) -> anyhow::Result<&'a Project> {
+    if let Some(project) = projects.iter().find(|p| p.id == name) {
+        return Ok(project);
+    }
+    match projects
+        .iter()
+        .filter(|p| p.name == name)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => anyhow::bail!("No project with the name or id {name} found"),
+        [project] => Ok(project),
+        several => anyhow::bail!(
+            "Several projects are named {name}, select one by its id: {}",
+            several
+                .iter()
+                .map(|p| p.id.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
 #[expect(clippy::print_stdout, reason = "No other way to show the stats")]
-pub fn show(projects: &[Project]) {
+fn print(projects: &[Project], options: &StatsOptions, dirs: &UserDirs) -> anyhow::Result<()> {
+    let selected: Vec<Project>;
+    let projects = if let Some(name) = &options.project {
+        selected = vec![select(projects, name)?.clone()];
+        &selected
+    } else {
+        projects
+    };
+
+    if options.breakdown {
+        for project in projects {
+            println!(
+                "{} ({}): {}",
+                project.name,
+                project.id,
+                project.path.join("target").display()
+            );
+            println!("{}", breakdown::render(project, options));
+        }
+        return Ok(());
+    }
+
+    if options.crates {
+        println!("{}", crates::render(projects, options));
+        return Ok(());
+    }
+
+    if let Some(since) = options.diff {
+        let snapshots = history::load(&history::path(dirs))?;
+        let now = SystemTime::now();
+        let snapshot = history::find(&snapshots, since, now).context(
+            "No matching snapshot in the size history, record one with `cargo wash stats --record`",
+        )?;
+        if snapshot.size_mode != options.options.size_mode {
+            log::warn!("The snapshot was recorded with another --size-mode");
+        }
+        let age = now.duration_since(snapshot.time()).unwrap_or_default();
+        println!(
+            "Compared with the snapshot from {} ago",
+            utility::format_duration(age)
+        );
+        let scope = Scope::of(projects, options, &Config::path(dirs))?;
+        println!("{}", diff::render(projects, snapshot, &scope, options));
+        return Ok(());
+    }
+
+    println!("{}", render(projects, options)?);
+
+    // The notes explain the table, consumers of the other formats have no use for them
+    if options.format != StatsFormat::Table {
+        return Ok(());
+    }
+
+    let sorted_projects = sort(projects, options);
+    let shown = shown(&sorted_projects, options);
+    if shown.len() < projects.len() {
+        let shown_size = total_size_of_projects(shown);
+        let total_size = total_size_of_projects(projects);
+        log::info!(
+            "The {} projects shown account for {} ({:.1}%) of the total {} of {} projects",
+            shown.len(),
+            Size::with_units(shown_size, options.options.units),
+            percentage(shown_size, total_size),
+            Size::with_units(total_size, options.options.units),
+            projects.len()
+        );
+    }
+
+    let mismatched = projects
+        .iter()
+        .filter(|p| {
+            p.toolchain
+                .as_ref()
+                .is_some_and(|t| t.mismatch == Some(true))
+        })
+        .count();
+    if mismatched > 0 {
+        log::info!(
+            "{mismatched} projects were built with another toolchain than the pinned one, \
+             their artifacts are rebuilt by the next build anyway"
+        );
+    }
+
+    if projects.iter().any(|p| p.cached) {
+        log::info!("Sizes marked with * are cached, use --no-cache to recalculate them");
+    }
+
+    let shared = data::duplicate_link_size(projects);
+    if shared > 0 {
+        log::info!(
+            "{} is hard-linked between projects and counted once in the total, \
+             it is only freed once every project sharing it is cleaned",
+            Size::with_units(shared, options.options.units)
+        );
+    }
+    Ok(())
+}
+
+/// Renders the stats of the projects in the selected format
+pub fn render(projects: &[Project], options: &StatsOptions) -> anyhow::Result<String> {
+    if options.tree {
+        return Ok(tree::render(projects, options));
+    }
+
+    let sorted_projects = sort(projects, options);
+    let shown = shown(&sorted_projects, options);
+
+    match options.format {
+        StatsFormat::Table => {
+            let (header, rows) = rows(shown, projects, options);
+            let mut table = Table::new();
+            table.load_preset(UTF8_FULL_CONDENSED);
+            table.set_header(header);
+            table.add_rows(rows);
+            Ok(table.to_string())
+        }
+        StatsFormat::Json => json(shown, projects),
+        StatsFormat::Csv => Ok(csv(shown, options)),
+        StatsFormat::Markdown => Ok(markdown(shown, projects, options)),
+    }
+}
+
+/// Sorts the projects by the selected key, ties are ordered by name
+fn sort(projects: &[Project], options: &StatsOptions) -> Vec<Project> {
     let mut sorted_projects: Vec<Project> = projects.to_vec();
-    sorted_projects.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-    let mut table = Table::new();
-    table.load_preset(UTF8_FULL_CONDENSED);
-    table.set_header(vec!["Project", "Size", "Path"]);
-
-    for project in sorted_projects {
-        table.add_row(vec![
-            &project.name,
-            &project.size.to_string(),
-            &project.path.to_string_lossy().to_string(),
+    sorted_projects.sort_by_key(|project| (project.name.to_lowercase(), project.id.clone()));
+    match options.sort {
+        StatsSort::Name => {}
+        StatsSort::Size => sorted_projects.sort_by_key(|project| Reverse(project.size)),
+        // Projects that were never built have no age and come last
+        StatsSort::Age => sorted_projects
+            .sort_by_key(|project| (project.last_built.is_none(), project.last_built)),
+        StatsSort::Path => sorted_projects.sort_by(|a, b| a.path.cmp(&b.path)),
+    }
+    if options.reverse {
+        sorted_projects.reverse();
+    }
+    sorted_projects
+}
+
+/// Returns the projects to show, the first `--top` ones if given
+fn shown<'a>(sorted_projects: &'a [Project], options: &StatsOptions) -> &'a [Project] {
+    let count = options
+        .top
+        .map_or(sorted_projects.len(), |top| top.min(sorted_projects.len()));
+    &sorted_projects[..count]
+}
+
+/// Returns the header and rows shown in the table formats, ending with a subtotal
+/// of the shown projects if some are hidden and the total of all projects
+fn rows(
+    shown: &[Project],
+    projects: &[Project],
+    options: &StatsOptions,
+) -> (Vec<&'static str>, Vec<Vec<String>>) {
+    let units = options.options.units;
+    let now = SystemTime::now();
+    let mut header = vec!["Project", "Version", "Id", "Size", "Last built"];
+    if options.long {
+        header.extend([
+            "Apparent",
+            "On disk",
+            "Hard-linked",
+            "Shared",
+            "Toolchain",
+            "rust-version",
+            "Built with",
         ]);
     }
+    if options.git {
+        header.extend(["Branch", "Git", "Upstream"]);
+    }
+    header.push("Path");
+
+    let mut rows = vec![];
+    for project in shown {
+        let mut size = size_cell(project.size, project.size_errors, units);
+        if project.cached {
+            size.push_str(" *");
+        }
+        let mut row = vec![
+            project.name.clone(),
+            project.version.clone().unwrap_or_else(|| "-".to_owned()),
+            project.id.clone(),
+            size,
+            utility::format_age(project.last_built, now),
+        ];
+        if options.long {
+            row.extend(
+                [
+                    project.apparent_size,
+                    project.disk_size,
+                    project.hardlinked,
+                    project.shared,
+                ]
+                .map(|bytes| Size::with_units(bytes, units).to_string()),
+            );
+            let toolchain = project.toolchain.clone().unwrap_or_default();
+            row.extend([
+                toolchain.pinned.clone().unwrap_or_else(|| "-".to_owned()),
+                toolchain
+                    .rust_version
+                    .clone()
+                    .unwrap_or_else(|| "-".to_owned()),
+                toolchain.built_with_label(),
+            ]);
+        }
+        if options.git {
+            row.extend(git_cells(project.git.as_ref()));
+        }
+        row.push(project.path.to_string_lossy().to_string());
+        rows.push(row);
+    }
+
+    if shown.len() < projects.len() {
+        rows.push(total_row("Shown", &total(shown), options));
+    }
+    rows.push(total_row("Total", &total(projects), options));
+
+    (header, rows)
+}
+
+fn total_row(label: &str, total: &Total, options: &StatsOptions) -> Vec<String> {
+    let units = options.options.units;
+    let mut row = vec![
+        label.to_owned(),
+        String::new(),
+        String::new(),
+        size_cell(total.size, total.size_errors, units),
+        String::new(),
+    ];
+    if options.long {
+        row.extend(
+            [
+                total.apparent_size,
+                total.disk_size,
+                total.hardlinked,
+                total.shared,
+            ]
+            .map(|bytes| Size::with_units(bytes, units).to_string()),
+        );
+        row.extend([String::new(), String::new(), String::new()]);
+    }
+    if options.git {
+        row.extend([String::new(), String::new(), String::new()]);
+    }
+    row.push(format!("{} projects", total.projects));
+    row
+}
+
+/// Formats the branch, the state of the work tree and the distance to the upstream branch
+fn git_cells(git: Option<&GitStatus>) -> [String; 3] {
+    let Some(git) = git else {
+        return ["-".to_owned(), "no repository".to_owned(), "-".to_owned()];
+    };
+    let state = match git.dirty {
+        Some(true) => "dirty",
+        Some(false) => "clean",
+        None => "unknown",
+    };
+    [
+        git.branch
+            .clone()
+            .unwrap_or_else(|| "(detached)".to_owned()),
+        state.to_owned(),
+        git.upstream_label(),
+    ]
+}
+
+fn total(projects: &[Project]) -> Total {
+    Total {
+        projects: projects.len(),
+        size: total_size_of_projects(projects),
+        apparent_size: projects.iter().map(|p| p.apparent_size).sum(),
+        disk_size: projects.iter().map(|p| p.disk_size).sum(),
+        hardlinked: projects.iter().map(|p| p.hardlinked).sum(),
+        shared: data::duplicate_link_size(projects),
+        size_errors: projects.iter().map(|p| p.size_errors).sum(),
+    }
+}
 
-    table.add_row(vec![
-        "Total",
-        &Size::to_size(total_size_of_projects(projects)).to_string(),
-        &format!("{} projects", projects.len()),
-    ]);
-    println!("{table}");
+fn json(shown: &[Project], projects: &[Project]) -> anyhow::Result<String> {
+    let report = Report {
+        projects: shown.iter().collect(),
+        shown: (shown.len() < projects.len()).then(|| total(shown)),
+        total: total(projects),
+    };
+    serde_json::to_string_pretty(&report).context("Failed to serialize stats")
+}
+
+/// One line per project, without a total row so every line has the same columns
+fn csv(projects: &[Project], options: &StatsOptions) -> String {
+    let mut header =
+        "name,version,id,path,size,apparent_size,disk_size,hardlinked,shared,size_errors,cached,last_built"
+            .to_owned();
+    if options.long {
+        header.push_str(",toolchain,rust_version,built_with,toolchain_mismatch");
+    }
+    if options.git {
+        header.push_str(",git_branch,git_dirty,git_ahead,git_behind");
+    }
+    let mut lines = vec![header];
+    for project in projects {
+        let mut fields = vec![
+            project.name.clone(),
+            project.version.clone().unwrap_or_default(),
+            project.id.clone(),
+            project.path.to_string_lossy().to_string(),
+            project.size.to_string(),
+            project.apparent_size.to_string(),
+            project.disk_size.to_string(),
+            project.hardlinked.to_string(),
+            project.shared.to_string(),
+            project.size_errors.to_string(),
+            project.cached.to_string(),
+            project
+                .last_built
+                .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs().to_string())
+                .unwrap_or_default(),
+        ];
+        if options.long {
+            let toolchain = project.toolchain.clone().unwrap_or_default();
+            fields.extend([
+                toolchain.pinned.unwrap_or_default(),
+                toolchain.rust_version.unwrap_or_default(),
+                toolchain.built_with.join(" "),
+                toolchain
+                    .mismatch
+                    .map(|mismatch| mismatch.to_string())
+                    .unwrap_or_default(),
+            ]);
+        }
+        if options.git {
+            let git = project.git.clone().unwrap_or_default();
+            let count = |count: Option<usize>| count.map(|c| c.to_string()).unwrap_or_default();
+            fields.extend([
+                git.branch.unwrap_or_default(),
+                git.dirty.map(|dirty| dirty.to_string()).unwrap_or_default(),
+                count(git.ahead),
+                count(git.behind),
+            ]);
+        }
+        let fields: Vec<_> = fields.iter().map(|field| csv_field(field)).collect();
+        lines.push(fields.join(","));
+    }
+    lines.join("\n")
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn markdown(shown: &[Project], projects: &[Project], options: &StatsOptions) -> String {
+    let (header, rows) = rows(shown, projects, options);
+    let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+
+    let mut lines = vec![
+        line(header.iter().map(|cell| (*cell).to_owned()).collect()),
+        line(header.iter().map(|_| "---".to_owned()).collect()),
+    ];
+    lines.extend(
+        rows.into_iter()
+            .map(|row| line(row.iter().map(|cell| cell.replace('|', "\\|")).collect())),
+    );
+    lines.join("\n")
+}
+
+/// Formats a size, marking it as partial if entries could not be read
+fn size_cell(bytes: u64, errors: usize, units: Units) -> String {
+    let size = Size::with_units(bytes, units);
+    if errors == 0 {
+        size.to_string()
+    } else {
+        format!("{size} (partial, {errors} errors)")
+    }
 }
@@ -0,0 +1,360 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+    process::Command,
+};
+
+use anyhow::Context as _;
+use comfy_table::{Table, presets::UTF8_FULL_CONDENSED};
+use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
+use serde::Deserialize;
+
+use crate::{
+    cli::opts::{self, ToolchainsOptions},
+    config::Config,
+    data::{Project, Size},
+    toolchain,
+    utility::{self, UserDirs},
+};
+
+/// Number of projects or directories listed before the rest is only counted
+const MAX_LISTED_PROJECTS: usize = 3;
+
+/// The parts of the rustup settings that reference toolchains
+#[derive(Deserialize, Default)]
+struct Settings {
+    default_toolchain: Option<String>,
+    /// Toolchains set with `rustup override`, keyed by directory
+    #[serde(default)]
+    overrides: BTreeMap<String, String>,
+}
+
+/// A toolchain installed in the rustup home
+pub struct Installed {
+    /// Directory name, like `nightly-2023-01-01-x86_64-unknown-linux-gnu`
+    pub name: String,
+    pub size: u64,
+    /// Ids of the projects pinning the toolchain
+    pub projects: Vec<String>,
+    pub default: bool,
+    /// Directories with a `rustup override` for the toolchain
+    pub overrides: Vec<String>,
+    /// A symlink, like the toolchains added with `rustup toolchain link`
+    pub linked: bool,
+}
+
+impl Installed {
+    /// Whether the toolchain was linked or is not named after a release channel,
+    /// custom toolchains cannot be installed again and are never uninstalled
+    pub fn custom(&self) -> bool {
+        self.linked || !is_release_channel(&split_toolchain_name(&self.name).0)
+    }
+
+    /// Whether nothing keeps the toolchain, so it can be uninstalled
+    pub fn unreferenced(&self) -> bool {
+        self.projects.is_empty() && !self.default && self.overrides.is_empty() && !self.custom()
+    }
+}
+
+/// Lists the installed toolchains with the projects pinning them
+/// and uninstalls the unreferenced ones with `--remove-unreferenced`
+#[expect(clippy::print_stdout, reason = "No other way to show the toolchains")]
+pub fn run(
+    projects: &[Project],
+    options: &ToolchainsOptions,
+    rustup_home: &Path,
+    dirs: &UserDirs,
+) -> anyhow::Result<()> {
+    log::info!("Rustup home: {}", rustup_home.display());
+    let toolchains = toolchains(projects, options, rustup_home);
+    println!("{}", render(&toolchains, options));
+
+    let unreferenced: Vec<&Installed> = toolchains
+        .iter()
+        .filter(|toolchain| toolchain.unreferenced())
+        .collect();
+    if !options.remove_unreferenced {
+        if !unreferenced.is_empty() {
+            log::info!(
+                "{} toolchains are not referenced, remove them with --remove-unreferenced",
+                unreferenced.len()
+            );
+        }
+        return Ok(());
+    }
+    // Scanning the current directory by default would find few pins and mark almost everything
+    let scanned = scanned_roots(options, dirs)?.context(
+        "--remove-unreferenced needs to know where the projects are, \
+         pass --path or --projects-from, or register roots with `cargo wash roots add`",
+    )?;
+    // Projects below the depth limit would count as not pinning anything
+    let limited = opts::depth_limited(&options.search_options(), dirs)?;
+    if !limited.is_empty() {
+        anyhow::bail!(
+            "The search for pins stopped at --max-depth {} in {}, \
+             pass a larger --max-depth so no project is missed",
+            options.options.max_depth,
+            listed(limited.iter().map(|dir| dir.display().to_string()))
+        );
+    }
+    if unreferenced.is_empty() {
+        log::info!("Every toolchain is referenced by the projects in {scanned}");
+        return Ok(());
+    }
+
+    let names: Vec<&str> = unreferenced.iter().map(|t| t.name.as_str()).collect();
+    let size = Size::with_units(
+        unreferenced.iter().map(|t| t.size).sum(),
+        options.options.units,
+    );
+    if options.dry_run {
+        log::info!(
+            "Would uninstall {} and free {size}, no project in {scanned} pins them",
+            names.join(", ")
+        );
+        return Ok(());
+    }
+    if !utility::confirm(
+        &format!(
+            "No project in {scanned} pins {}. Uninstall them and free {size}?",
+            names.join(", ")
+        ),
+        options.yes,
+    )? {
+        log::info!("Nothing uninstalled");
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for toolchain in unreferenced {
+        if cfg!(test) {
+            log::debug!("Would uninstall: {}", toolchain.name);
+            continue;
+        }
+        let result = Command::new("rustup")
+            .args(["toolchain", "uninstall"])
+            .arg(&toolchain.name)
+            .env("RUSTUP_HOME", rustup_home)
+            .output();
+        match result {
+            Ok(output) if output.status.success() => {
+                log::info!("Uninstalled {}", toolchain.name);
+            }
+            Ok(output) => {
+                log::error!(
+                    "Failed to uninstall {}: {}",
+                    toolchain.name,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                failed += 1;
+            }
+            Err(error) => {
+                log::error!("Failed to uninstall {}: {error}", toolchain.name);
+                failed += 1;
+            }
+        }
+    }
+    anyhow::ensure!(
+        failed == 0,
+        "Some toolchains ({failed}) could not be uninstalled"
+    );
+    Ok(())
+}
+
+/// Collects the toolchains in the rustup home with what references them, sorted by name
+pub fn toolchains(
+    projects: &[Project],
+    options: &ToolchainsOptions,
+    rustup_home: &Path,
+) -> Vec<Installed> {
+    let settings_path = rustup_home.join("settings.toml");
+    let settings: Settings = fs::read_to_string(&settings_path)
+        .ok()
+        .and_then(|content| {
+            toml::from_str(&content)
+                .inspect_err(|error| {
+                    log::warn!("Failed to parse {}: {error}", settings_path.display());
+                })
+                .ok()
+        })
+        .unwrap_or_default();
+
+    let pins: Vec<(String, String)> = projects
+        .par_iter()
+        .filter_map(|project| {
+            toolchain::pinned_channel(&project.path).map(|channel| (channel, project.id.clone()))
+        })
+        .collect();
+
+    let dir = rustup_home.join("toolchains");
+    let mut toolchains: Vec<Installed> = linked_subdirs(&dir)
+        .into_iter()
+        .map(|(name, linked)| {
+            let path = dir.join(&name);
+            Installed {
+                // The target of a link is not in the rustup home
+                size: if linked {
+                    0
+                } else {
+                    utility::get_folder_size(&path).get(options.options.size_mode)
+                },
+                projects: pins
+                    .iter()
+                    .filter(|(channel, _)| matches(channel, &name))
+                    .map(|(_, id)| id.clone())
+                    .collect(),
+                default: settings
+                    .default_toolchain
+                    .as_deref()
+                    .is_some_and(|default| matches(default, &name)),
+                overrides: settings
+                    .overrides
+                    .iter()
+                    .filter(|(_, toolchain)| matches(toolchain, &name))
+                    .map(|(dir, _)| dir.clone())
+                    .collect(),
+                linked,
+                name,
+            }
+        })
+        .collect();
+    toolchains.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let missing: BTreeSet<&str> = pins
+        .iter()
+        .map(|(channel, _)| channel.as_str())
+        .filter(|channel| !toolchains.iter().any(|t| matches(channel, &t.name)))
+        .collect();
+    if !missing.is_empty() {
+        log::info!(
+            "Pinned but not installed: {}",
+            missing.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+    toolchains
+}
+
+/// Describes where the projects were searched, `None` if no `--path`, `--projects-from`
+/// or registered root was given and only the current directory was scanned
+fn scanned_roots(options: &ToolchainsOptions, dirs: &UserDirs) -> anyhow::Result<Option<String>> {
+    if let Some(file) = &options.options.projects_from {
+        return Ok(Some(format!("the list {}", file.display())));
+    }
+    let roots = if options.options.path.is_empty() {
+        Config::load(&Config::path(dirs))?.roots
+    } else {
+        options.options.path.clone()
+    };
+    Ok((!roots.is_empty()).then(|| {
+        roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }))
+}
+
+/// Lists the names of the directories in `dir`, with whether they are a symlink to a directory
+fn linked_subdirs(dir: &Path) -> Vec<(String, bool)> {
+    fs::read_dir(dir).map_or_else(
+        |_| vec![],
+        |entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| {
+                    (
+                        entry.file_name().to_string_lossy().to_string(),
+                        entry.file_type().is_ok_and(|t| t.is_symlink()),
+                    )
+                })
+                .collect()
+        },
+    )
+}
+
+/// Whether a channel is one rustup can install, like `stable`, `nightly-2023-01-01` or `1.80.0`
+fn is_release_channel(channel: &str) -> bool {
+    let name = channel.split('-').next().unwrap_or(channel);
+    let parts: Vec<&str> = name.split('.').collect();
+    matches!(name, "stable" | "beta" | "nightly")
+        || ((2..=3).contains(&parts.len())
+            && parts
+                .iter()
+                .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit())))
+}
+
+/// Splits a toolchain name like `nightly-2023-01-01-x86_64-unknown-linux-gnu`
+/// into the channel with its date and the host triple, if there is one
+pub fn split_toolchain_name(name: &str) -> (String, Option<String>) {
+    let parts: Vec<&str> = name.split('-').collect();
+    let is_date = parts.len() >= 4
+        && parts[1..4]
+            .iter()
+            .zip([4, 2, 2])
+            .all(|(part, len)| part.len() == len && part.bytes().all(|b| b.is_ascii_digit()));
+    let channel_parts = if is_date { 4 } else { 1 };
+    let host = parts[channel_parts.min(parts.len())..].join("-");
+    (
+        parts[..channel_parts.min(parts.len())].join("-"),
+        (!host.is_empty()).then_some(host),
+    )
+}
+
+/// Whether the toolchain `reference`, with or without a host triple, names the installed toolchain.
+/// Without a host rustup uses the default host, every host of the channel counts as referenced.
+pub fn matches(reference: &str, installed: &str) -> bool {
+    let (channel, host) = split_toolchain_name(reference);
+    let (installed_channel, installed_host) = split_toolchain_name(installed);
+    reference == installed
+        || (channel == installed_channel && (host.is_none() || host == installed_host))
+}
+
+/// Joins the first few entries, only counting the rest
+fn listed(entries: impl ExactSizeIterator<Item = String>) -> String {
+    let count = entries.len();
+    let mut listed: Vec<String> = entries.take(MAX_LISTED_PROJECTS).collect();
+    if count > MAX_LISTED_PROJECTS {
+        listed.push(format!("{} more", count - MAX_LISTED_PROJECTS));
+    }
+    listed.join(", ")
+}
+
+fn render(toolchains: &[Installed], options: &ToolchainsOptions) -> String {
+    let units = options.options.units;
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL_CONDENSED);
+    table.set_header(["Toolchain", "Size", "Pinned by", "Status"]);
+    for toolchain in toolchains {
+        let status = if toolchain.default {
+            "default"
+        } else if !toolchain.overrides.is_empty() {
+            "override"
+        } else if toolchain.linked {
+            "linked"
+        } else if toolchain.custom() {
+            "custom"
+        } else if toolchain.projects.is_empty() {
+            "unreferenced"
+        } else {
+            ""
+        };
+        table.add_row([
+            toolchain.name.clone(),
+            Size::with_units(toolchain.size, units).to_string(),
+            listed(toolchain.projects.iter().cloned()),
+            status.to_owned(),
+        ]);
+    }
+    table.add_row([
+        "Total".to_owned(),
+        Size::with_units(toolchains.iter().map(|t| t.size).sum(), units).to_string(),
+        String::new(),
+        format!(
+            "{} unreferenced",
+            toolchains.iter().filter(|t| t.unreferenced()).count()
+        ),
+    ]);
+    table.to_string()
+}
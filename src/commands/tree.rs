@@ -0,0 +1,156 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Component,
+};
+
+use crate::{
+    cli::opts::StatsOptions,
+    commands::{percentage, total_size_of_projects},
+    data::{Project, Size},
+};
+
+/// A directory below the search path, or a project, with all projects below it
+#[derive(Default)]
+struct Node {
+    projects: Vec<Project>,
+    children: BTreeMap<String, Self>,
+    /// Whether a project lives in this directory
+    is_project: bool,
+}
+
+impl Node {
+    fn insert(&mut self, segments: &[&str], project: &Project) {
+        self.projects.push(project.clone());
+        match segments.split_first() {
+            Some((first, rest)) => self
+                .children
+                .entry((*first).to_owned())
+                .or_default()
+                .insert(rest, project),
+            None => self.is_project = true,
+        }
+    }
+
+    /// Size of all projects below the node, counting files hard-linked between them once
+    fn size(&self) -> u64 {
+        total_size_of_projects(&self.projects)
+    }
+}
+
+/// Renders the projects as a tree of the directories they are in relative
+/// to the search path, with every directory showing the size of all projects
+/// below it, so many small projects stand out as much as a single large one.
+/// With several search paths, each of them is a directory at the top of the tree.
+pub fn render(projects: &[Project], options: &StatsOptions) -> String {
+    let roots: BTreeSet<_> = projects.iter().filter_map(|p| p.root.as_ref()).collect();
+    let mut root = Node::default();
+    for project in projects {
+        let segments = segments(project, roots.len() > 1);
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        root.insert(&segments, project);
+    }
+
+    let total = root.size();
+    let mut lines = vec![line(&root, ".", total, "", options)];
+    render_children(&root, total, "", 1, options, &mut lines);
+    lines.join("\n")
+}
+
+/// Returns the directories from the search root down to the project, led by the root
+/// itself with several roots. Without a known root the id is split instead.
+fn segments(project: &Project, several_roots: bool) -> Vec<String> {
+    let Some((root, relative)) = project
+        .root
+        .as_ref()
+        .and_then(|root| Some((root, project.path.strip_prefix(root).ok()?)))
+    else {
+        return project
+            .id
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(str::to_owned)
+            .collect();
+    };
+
+    let mut segments: Vec<_> = relative
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            Component::Prefix(_)
+            | Component::RootDir
+            | Component::CurDir
+            | Component::ParentDir => None,
+        })
+        .collect();
+    if several_roots {
+        segments.insert(0, root.display().to_string());
+    } else if segments.is_empty() {
+        // The search path is a project itself
+        segments.push(project.id.clone());
+    }
+    segments
+}
+
+fn render_children(
+    node: &Node,
+    total: u64,
+    prefix: &str,
+    depth: usize,
+    options: &StatsOptions,
+    lines: &mut Vec<String>,
+) {
+    if options.depth.is_some_and(|max_depth| depth > max_depth) {
+        return;
+    }
+
+    // Largest first, like `dust` or `ncdu`
+    let mut children: Vec<_> = node
+        .children
+        .iter()
+        .map(|(n, c)| (c.size(), n, c))
+        .collect();
+    children.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+
+    let count = children.len();
+    for (position, (_, name, child)) in children.into_iter().enumerate() {
+        let last = position + 1 == count;
+        let branch = if last { "└── " } else { "├── " };
+        lines.push(line(
+            child,
+            name,
+            total,
+            &format!("{prefix}{branch}"),
+            options,
+        ));
+
+        let indent = if last { "    " } else { "│   " };
+        render_children(
+            child,
+            total,
+            &format!("{prefix}{indent}"),
+            depth + 1,
+            options,
+            lines,
+        );
+    }
+}
+
+fn line(node: &Node, name: &str, total: u64, prefix: &str, options: &StatsOptions) -> String {
+    let size = node.size();
+    let share = percentage(size, total);
+
+    let package = match node.projects.as_slice() {
+        [project] if node.is_project && project.name != name => format!(" [{}]", project.name),
+        _ => String::new(),
+    };
+    let count = match node.projects.len() {
+        1 if node.is_project => String::new(),
+        1 => " (1 project)".to_owned(),
+        count => format!(" ({count} projects)"),
+    };
+
+    format!(
+        "{:>12} {share:>5.1}%  {prefix}{name}{package}{count}",
+        Size::with_units(size, options.options.units).to_string()
+    )
+}
@@ -0,0 +1,46 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+
+use crate::utility::UserDirs;
+
+/// Persistent user configuration, stored as TOML in the user config dir
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Config {
+    /// Default search paths used when no `--path` is given
+    pub roots: Vec<PathBuf>,
+}
+
+impl Config {
+    /// Returns the location of the config file in the user directories
+    pub fn path(dirs: &UserDirs) -> PathBuf {
+        dirs.config.join("config.toml")
+    }
+
+    /// Loads the config from the given file, returning the default config if it does not exist
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .context(format!("Failed to read config file: {}", path.display()))?;
+        toml::from_str(&content).context(format!("Failed to parse config file: {}", path.display()))
+    }
+
+    /// Writes the config to the given file, creating parent directories as needed
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context(format!(
+                "Failed to create config directory: {}",
+                parent.display()
+            ))?;
+        }
+        let content = toml::to_string_pretty(self).context("Failed to serialize config")?;
+        fs::write(path, content).context(format!("Failed to write config file: {}", path.display()))
+    }
+}
@@ -1,21 +1,67 @@
-use core::fmt::{self, Display};
-use std::path::{Path, PathBuf};
+use core::{
+    fmt::{self, Display},
+    ops::AddAssign,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{git::GitStatus, toolchain::Toolchain};
+
+/// Represents the unit system used to display sizes
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Units {
+    /// Binary units with 1024 multipliers: KiB, MiB, GiB, TiB
+    #[default]
+    Iec,
+    /// Decimal units with 1000 multipliers: kB, MB, GB, TB
+    Si,
+    /// Exact number of bytes
+    Bytes,
+}
 
 /// Represents the size unit
 /// B = Bytes
-/// KB = Kilobytes
-/// MB = Megabytes
-/// GB = Gigabytes
-#[derive(strum_macros::Display, Clone, Copy, PartialEq, Eq, PartialOrd, Debug)]
+/// KiB/MiB/GiB/TiB = binary (IEC) multiples of 1024
+/// kB/MB/GB/TB = decimal (SI) multiples of 1000
+#[derive(strum_macros::Display, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Debug)]
 pub enum SizeUnit {
     B,
-    GB,
+    KiB,
+    MiB,
+    GiB,
+    TiB,
+    #[strum(serialize = "kB")]
+    #[serde(rename = "kB")]
     KB,
     MB,
+    GB,
+    TB,
+}
+
+impl SizeUnit {
+    /// Returns the number of bytes in one unit
+    pub const fn multiplier(self) -> u64 {
+        match self {
+            Self::B => 1,
+            Self::KiB => 1 << 10,
+            Self::MiB => 1 << 20,
+            Self::GiB => 1 << 30,
+            Self::TiB => 1 << 40,
+            Self::KB => 1_000,
+            Self::MB => 1_000_000,
+            Self::GB => 1_000_000_000,
+            Self::TB => 1_000_000_000_000,
+        }
+    }
 }
 
-/// Represents a size in bytes with a unit
-#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+/// Represents a size in bytes with a unit, used for display only
+#[derive(Serialize, Clone, Copy, PartialEq, PartialOrd, Debug)]
 pub struct Size {
     pub unit: SizeUnit,
     pub value: f64,
@@ -26,59 +72,250 @@ impl Size {
         Self { unit, value }
     }
 
-    /// Returns the size in bytes
-    #[expect(clippy::cast_possible_truncation, reason = "Ok here")]
-    #[expect(clippy::cast_sign_loss, reason = "Ok here")]
-    pub fn size_in_bytes(&self) -> u64 {
-        let multiplier = match self.unit {
-            SizeUnit::B => 1,
-            SizeUnit::KB => 1024,
-            SizeUnit::MB => 1024 * 1024,
-            SizeUnit::GB => 1024 * 1024 * 1024,
+    /// Converts a size in bytes to a `Size` struct with the largest unit of the given system
+    #[expect(clippy::cast_precision_loss, reason = "Ok here")]
+    pub fn with_units(bytes: u64, units: Units) -> Self {
+        let candidates: &[SizeUnit] = match units {
+            Units::Iec => &[SizeUnit::TiB, SizeUnit::GiB, SizeUnit::MiB, SizeUnit::KiB],
+            Units::Si => &[SizeUnit::TB, SizeUnit::GB, SizeUnit::MB, SizeUnit::KB],
+            Units::Bytes => &[],
         };
-        (self.value * f64::from(multiplier)) as u64
+
+        candidates
+            .iter()
+            .find(|unit| bytes >= unit.multiplier())
+            .map_or_else(
+                || Self::new(bytes as f64, SizeUnit::B),
+                |unit| Self::new(bytes as f64 / unit.multiplier() as f64, *unit),
+            )
     }
+}
 
-    /// Converts a size in bytes to a `Size` struct
-    #[expect(clippy::cast_precision_loss, reason = "Ok here")]
-    pub fn to_size(bytes: u64) -> Self {
-        const KB: u64 = 1024;
-        const MB: u64 = KB * 1024;
-        const GB: u64 = MB * 1024;
-
-        if bytes >= GB {
-            Self::new(bytes as f64 / GB as f64, SizeUnit::GB)
-        } else if bytes >= MB {
-            Self::new(bytes as f64 / MB as f64, SizeUnit::MB)
-        } else if bytes >= KB {
-            Self::new(bytes as f64 / KB as f64, SizeUnit::KB)
+impl Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.unit == SizeUnit::B {
+            write!(f, "{:.0} {}", self.value, self.unit)
         } else {
-            Self::new(bytes as f64, SizeUnit::B)
+            write!(f, "{:.2} {}", self.value, self.unit)
         }
     }
 }
 
-impl Display for Size {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:.2} {}", self.value, self.unit)
+/// Represents how the size of files is measured
+#[derive(clap::ValueEnum, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeMode {
+    /// The length of the files, as shown by `ls`
+    #[default]
+    Apparent,
+    /// The blocks allocated on disk, as shown by `du`
+    Disk,
+}
+
+/// Identifies a file on disk, used to count files with several hard links once
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Inode {
+    pub dev: u64,
+    pub ino: u64,
+}
+
+/// The result of walking a folder: the bytes counted
+/// and the number of entries that could not be read
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct FolderSize {
+    /// Sum of the file lengths
+    pub bytes: u64,
+    /// Sum of the blocks allocated on disk
+    pub disk_bytes: u64,
+    pub errors: usize,
+    /// Files with several hard links and their apparent and on-disk size, counted once each
+    pub linked: HashMap<Inode, (u64, u64)>,
+    /// Apparent size of additional hard links to files that were already counted
+    pub hardlinked_bytes: u64,
+    /// On-disk size of additional hard links to files that were already counted
+    pub hardlinked_disk_bytes: u64,
+    /// Walked directories with the mtime they had before they were read
+    pub dirs: Vec<(PathBuf, Option<SystemTime>)>,
+    /// Newest modification time of a file
+    pub newest: Option<SystemTime>,
+}
+
+impl FolderSize {
+    pub const fn get(&self, mode: SizeMode) -> u64 {
+        match mode {
+            SizeMode::Apparent => self.bytes,
+            SizeMode::Disk => self.disk_bytes,
+        }
+    }
+
+    /// Returns the size of the hard links that were not counted again
+    pub const fn hardlinked(&self, mode: SizeMode) -> u64 {
+        match mode {
+            SizeMode::Apparent => self.hardlinked_bytes,
+            SizeMode::Disk => self.hardlinked_disk_bytes,
+        }
+    }
+
+    /// Counts a file, or only records it as hard link if its inode was already counted
+    pub fn add_file(&mut self, inode: Option<Inode>, bytes: u64, disk_bytes: u64) {
+        if let Some(inode) = inode {
+            if self.linked.contains_key(&inode) {
+                self.hardlinked_bytes += bytes;
+                self.hardlinked_disk_bytes += disk_bytes;
+                return;
+            }
+            self.linked.insert(inode, (bytes, disk_bytes));
+        }
+        self.bytes += bytes;
+        self.disk_bytes += disk_bytes;
+    }
+}
+
+impl AddAssign for FolderSize {
+    fn add_assign(&mut self, other: Self) {
+        self.bytes += other.bytes;
+        self.disk_bytes += other.disk_bytes;
+        self.errors += other.errors;
+        self.hardlinked_bytes += other.hardlinked_bytes;
+        self.hardlinked_disk_bytes += other.hardlinked_disk_bytes;
+        self.dirs.extend(other.dirs);
+        self.newest = self.newest.max(other.newest);
+
+        // Files linked from both sides were counted twice
+        for (inode, (bytes, disk_bytes)) in other.linked {
+            if self.linked.insert(inode, (bytes, disk_bytes)).is_some() {
+                self.bytes -= bytes;
+                self.disk_bytes -= disk_bytes;
+                self.hardlinked_bytes += bytes;
+                self.hardlinked_disk_bytes += disk_bytes;
+            }
+        }
+    }
+}
+
+/// A file found by a folder walk
+#[derive(Clone, Debug)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    /// Set for files with several hard links
+    pub inode: Option<Inode>,
+    pub bytes: u64,
+    pub disk_bytes: u64,
+}
+
+/// The files found by walking a folder
+/// and the number of entries that could not be read
+#[derive(Clone, Default, Debug)]
+pub struct FileList {
+    pub files: Vec<FileEntry>,
+    pub errors: usize,
+}
+
+impl AddAssign for FileList {
+    fn add_assign(&mut self, other: Self) {
+        self.files.extend(other.files);
+        self.errors += other.errors;
     }
 }
 
 /// Represents a Rust project
 /// with its name, path, and size
-#[derive(Clone, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Project {
+    /// Package name from `Cargo.toml`, or the directory name for virtual workspaces
     pub name: String,
+    /// Unique display id, the path relative to the scan root
+    pub id: String,
+    pub version: Option<String>,
     pub path: PathBuf,
-    pub size: Size,
+    /// The search root the project was found below, `None` if it is not known
+    #[serde(skip)]
+    pub root: Option<PathBuf>,
+    /// Size of the target directory in bytes, in the selected `SizeMode`
+    pub size: u64,
+    pub apparent_size: u64,
+    pub disk_size: u64,
+    /// Files with several hard links and their size, each counted once in `size`
+    #[serde(skip)]
+    pub linked: Vec<(Inode, u64)>,
+    /// Size of hard links within the target directory that were not counted again
+    pub hardlinked: u64,
+    /// Size of files hard-linked with other projects, cleaning only this project does not free it
+    pub shared: u64,
+    /// Number of entries that could not be read, the size is partial if this is not zero
+    pub size_errors: usize,
+    /// Whether the size was reused from the size cache or index instead of being calculated
+    pub cached: bool,
+    /// Newest modification time of a file in the target directory, `None` if it has no files
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub last_built: Option<SystemTime>,
+    /// Toolchain information, only read for `stats --long`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toolchain: Option<Toolchain>,
+    /// State of the git repository, only read for `stats --git` and the git filters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitStatus>,
 }
 
 impl Project {
     pub fn new<P: AsRef<Path>>(name: &str, path: P, size: u64) -> Self {
         Self {
             name: name.to_owned(),
+            id: name.to_owned(),
+            version: None,
             path: path.as_ref().to_path_buf(),
-            size: Size::to_size(size),
+            root: None,
+            size,
+            apparent_size: size,
+            disk_size: size,
+            linked: vec![],
+            hardlinked: 0,
+            shared: 0,
+            size_errors: 0,
+            cached: false,
+            last_built: None,
+            toolchain: None,
+            git: None,
         }
     }
 }
+
+/// Serializes a time as seconds since the Unix epoch
+#[expect(clippy::ref_option, reason = "Signature required by serde")]
+fn serialize_timestamp<S: serde::Serializer>(
+    time: &Option<SystemTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    time.and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .serialize(serializer)
+}
+
+/// Returns how often files hard-linked between the given projects were counted
+/// more than once, i.e. the size to subtract from their sum
+pub fn duplicate_link_size(projects: &[Project]) -> u64 {
+    let mut counts: HashMap<Inode, (u64, u64)> = HashMap::new();
+    for (inode, size) in projects.iter().flat_map(|p| &p.linked) {
+        counts.entry(*inode).or_insert((0, *size)).0 += 1;
+    }
+    counts
+        .values()
+        .map(|(count, size)| (count - 1) * size)
+        .sum()
+}
+
+/// Sets `Project::shared` to the size of the files each project shares with others
+pub fn annotate_shared_links(projects: &mut [Project]) {
+    let mut counts: HashMap<Inode, usize> = HashMap::new();
+    for (inode, _) in projects.iter().flat_map(|p| &p.linked) {
+        *counts.entry(*inode).or_default() += 1;
+    }
+    for project in projects {
+        project.shared = project
+            .linked
+            .iter()
+            .filter(|(inode, _)| counts.get(inode).is_some_and(|count| *count > 1))
+            .map(|(_, size)| size)
+            .sum();
+    }
+}
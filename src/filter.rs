@@ -0,0 +1,69 @@
+use anyhow::Context as _;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::RegexSet;
+
+/// Decides which discovered projects are kept, based on the
+/// `--include`, `--exclude` and `--exclude-regex` options.
+///
+/// Every pattern is matched against the package name and against the path
+/// relative to the search root the project was found in (using `/` as separator).
+/// Unlike the project id, the path does not depend on the number of search roots.
+#[derive(Debug)]
+pub struct ProjectFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    exclude_regex: RegexSet,
+}
+
+impl Default for ProjectFilter {
+    fn default() -> Self {
+        Self {
+            include: None,
+            exclude: GlobSet::empty(),
+            exclude_regex: RegexSet::empty(),
+        }
+    }
+}
+
+impl ProjectFilter {
+    pub fn new(
+        include: &[String],
+        exclude: &[String],
+        exclude_regex: &[String],
+    ) -> anyhow::Result<Self> {
+        let include = if include.is_empty() {
+            None
+        } else {
+            Some(build_globset(include)?)
+        };
+
+        Ok(Self {
+            include,
+            exclude: build_globset(exclude)?,
+            exclude_regex: RegexSet::new(exclude_regex).context("Invalid exclude regex")?,
+        })
+    }
+
+    /// Returns `true` if a project with the given name and id should be kept
+    pub fn is_match(&self, name: &str, id: &str) -> bool {
+        let candidates = [name, id];
+
+        let included = self
+            .include
+            .as_ref()
+            .is_none_or(|set| candidates.iter().any(|c| set.is_match(c)));
+        let excluded = candidates
+            .iter()
+            .any(|c| self.exclude.is_match(c) || self.exclude_regex.is_match(c));
+
+        included && !excluded
+    }
+}
+
+fn build_globset(patterns: &[String]) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).context(format!("Invalid glob pattern: {pattern}"))?);
+    }
+    builder.build().context("Failed to build glob set")
+}
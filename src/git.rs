@@ -0,0 +1,116 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use rayon::iter::{IntoParallelRefMutIterator as _, ParallelIterator as _};
+use serde::Serialize;
+
+use crate::data::Project;
+
+/// The state of the git repository a project is in
+#[derive(Serialize, Clone, Default, PartialEq, Eq, Debug)]
+pub struct GitStatus {
+    /// Current branch, `None` for a detached HEAD
+    pub branch: Option<String>,
+    /// Whether the project directory has uncommitted changes or untracked files,
+    /// changes elsewhere in the repository are not counted. `None` if `git` could not be run.
+    pub dirty: Option<bool>,
+    /// Commits ahead of the upstream branch, `None` if the branch has no upstream
+    pub ahead: Option<usize>,
+    /// Commits behind the upstream branch, as known from the local refs
+    pub behind: Option<usize>,
+}
+
+impl GitStatus {
+    /// Reads the status of the repository the project is in, `None` if it is in none
+    pub fn read(project: &Path) -> Option<Self> {
+        let git_dir = git_dir(project)?;
+        let mut status = Self {
+            branch: head_branch(&git_dir),
+            ..Default::default()
+        };
+
+        // The work tree state and the commit graph need git itself, fetching is never done.
+        // Optional locks are disabled so the index is not rewritten while other tools use it.
+        // The pathspec limits the status to the project, several projects can share a repository.
+        match Command::new("git")
+            .arg("-C")
+            .arg(project)
+            .args(["status", "--porcelain=v2", "--branch", "--", "."])
+            .env("GIT_OPTIONAL_LOCKS", "0")
+            .output()
+        {
+            Ok(output) if output.status.success() => {
+                status.apply_porcelain(&String::from_utf8_lossy(&output.stdout));
+            }
+            Ok(output) => log::debug!(
+                "git status failed in {}: {}",
+                project.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(error) => log::debug!("Failed to run git: {error}"),
+        }
+        Some(status)
+    }
+
+    /// Sets the dirty state and the distance to the upstream branch
+    /// from the output of `git status --porcelain=v2 --branch`
+    pub fn apply_porcelain(&mut self, output: &str) {
+        let mut dirty = false;
+        for line in output.lines() {
+            if let Some(counts) = line.strip_prefix("# branch.ab ") {
+                let mut counts = counts
+                    .split_whitespace()
+                    .map(|count| count.trim_start_matches(['+', '-']).parse().ok());
+                self.ahead = counts.next().flatten();
+                self.behind = counts.next().flatten();
+            } else if !line.starts_with('#') && !line.is_empty() {
+                dirty = true;
+            }
+        }
+        self.dirty = Some(dirty);
+    }
+
+    /// Formats the distance to the upstream branch, like `2 ahead, 1 behind`
+    pub fn upstream_label(&self) -> String {
+        match (self.ahead.unwrap_or(0), self.behind.unwrap_or(0)) {
+            _ if self.ahead.is_none() && self.behind.is_none() => "-".to_owned(),
+            (0, 0) => "up to date".to_owned(),
+            (ahead, 0) => format!("{ahead} ahead"),
+            (0, behind) => format!("{behind} behind"),
+            (ahead, behind) => format!("{ahead} ahead, {behind} behind"),
+        }
+    }
+}
+
+/// Reads the git status of every project that does not have it yet
+pub fn read_all(projects: &mut [Project]) {
+    projects
+        .par_iter_mut()
+        .filter(|project| project.git.is_none())
+        .for_each(|project| project.git = GitStatus::read(&project.path));
+}
+
+/// Finds the git directory of the repository the project is in.
+/// Worktrees and submodules have a `.git` file pointing to their git directory.
+pub fn git_dir(project: &Path) -> Option<PathBuf> {
+    project.ancestors().find_map(|dir| {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        let content = fs::read_to_string(&dot_git).ok()?;
+        let git_dir = content.trim().strip_prefix("gitdir:")?.trim();
+        Some(dir.join(git_dir))
+    })
+}
+
+/// Reads the branch checked out in a git directory, `None` for a detached HEAD
+fn head_branch(git_dir: &Path) -> Option<String> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    head.trim()
+        .strip_prefix("ref: refs/heads/")
+        .map(str::to_owned)
+}
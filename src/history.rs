@@ -0,0 +1,137 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write as _,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::total_size_of_projects,
+    data::{Project, SizeMode},
+    utility::{self, UserDirs},
+};
+
+/// The sizes of all projects at one point in time, written by `stats --record`.
+/// The history file holds one snapshot per line, oldest first.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Snapshot {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    #[serde(default)]
+    pub size_mode: SizeMode,
+    pub total: u64,
+    pub projects: Vec<SnapshotProject>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SnapshotProject {
+    pub name: String,
+    pub id: String,
+    /// Canonical path, used to match the project between snapshots
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+impl Snapshot {
+    pub fn new(projects: &[Project], size_mode: SizeMode, time: SystemTime) -> Self {
+        Self {
+            timestamp: time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs()),
+            size_mode,
+            total: total_size_of_projects(projects),
+            projects: projects
+                .iter()
+                .map(|project| SnapshotProject {
+                    name: project.name.clone(),
+                    id: project.id.clone(),
+                    path: fs::canonicalize(&project.path).unwrap_or_else(|_| project.path.clone()),
+                    size: project.size,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn time(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(self.timestamp)
+    }
+}
+
+/// Which snapshot to compare with
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Since {
+    /// The most recent snapshot
+    Latest,
+    /// The most recent snapshot that is at least this old
+    Age(Duration),
+}
+
+impl Since {
+    /// Parses `latest` or a duration like `7d`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if value == "latest" {
+            Ok(Self::Latest)
+        } else {
+            utility::parse_duration(value).map(Self::Age)
+        }
+    }
+}
+
+/// Returns the location of the history file in the user directories
+pub fn path(dirs: &UserDirs) -> PathBuf {
+    dirs.data.join("history.jsonl")
+}
+
+/// Loads all snapshots from the history file, oldest first.
+/// Lines that cannot be parsed are skipped.
+pub fn load(path: &Path) -> anyhow::Result<Vec<Snapshot>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read history file: {}", path.display()))?;
+
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            serde_json::from_str(line)
+                .inspect_err(|error| {
+                    log::warn!("Ignoring corrupt snapshot in {}: {error}", path.display());
+                })
+                .ok()
+        })
+        .collect())
+}
+
+/// Appends a snapshot to the history file, creating it as needed
+pub fn append(path: &Path, snapshot: &Snapshot) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context(format!(
+            "Failed to create data directory: {}",
+            parent.display()
+        ))?;
+    }
+    let line = serde_json::to_string(snapshot).context("Failed to serialize snapshot")?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context(format!("Failed to open history file: {}", path.display()))?;
+    writeln!(file, "{line}").context(format!("Failed to write history file: {}", path.display()))
+}
+
+/// Returns the snapshot to compare with at `now`
+pub fn find(snapshots: &[Snapshot], since: Since, now: SystemTime) -> Option<&Snapshot> {
+    let Since::Age(age) = since else {
+        return snapshots.last();
+    };
+    let before = now.checked_sub(age)?;
+    snapshots
+        .iter()
+        .rev()
+        .find(|snapshot| snapshot.time() <= before)
+}
@@ -0,0 +1,259 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::opts::WASHIGNORE_FILE,
+    data::{Inode, Project, SizeMode},
+    utility::UserDirs,
+};
+
+/// Files that can exclude directories from discovery, relative to a walked directory.
+/// Editing them does not change the mtime of the directory.
+const IGNORE_FILES: [&str; 3] = [WASHIGNORE_FILE, ".gitignore", ".git/info/exclude"];
+
+/// On-disk cache of discovered projects, written by `cargo wash index`
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Index {
+    pub roots: Vec<IndexedRoot>,
+}
+
+/// The discovery settings a root was indexed with.
+/// A root indexed with other settings has to be walked again.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DiscoverySettings {
+    pub max_depth: usize,
+    pub gitignore: bool,
+    pub no_ignore: bool,
+}
+
+/// A scan root with the directories walked and the projects found below it
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IndexedRoot {
+    pub path: PathBuf,
+    pub settings: DiscoverySettings,
+    /// Walked directories (relative to the root), whose mtime changes when entries are added or removed
+    pub dirs: Vec<IndexedDir>,
+    pub projects: Vec<IndexedProject>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IndexedDir {
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    /// Hash of the ignore files in the directory, `None` if it has none
+    #[serde(default)]
+    pub ignore_hash: Option<u64>,
+}
+
+/// A project with its path relative to the root and its last known size
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IndexedProject {
+    pub path: PathBuf,
+    pub name: String,
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    pub target_dir: PathBuf,
+    pub size: u64,
+    #[serde(default)]
+    pub disk_size: u64,
+    #[serde(default)]
+    pub size_errors: usize,
+    /// Files with several hard links and their size in the size mode the project was indexed with
+    #[serde(default)]
+    pub linked: Vec<(Inode, u64)>,
+    #[serde(default)]
+    pub hardlinked: u64,
+    #[serde(default)]
+    pub size_mode: SizeMode,
+    #[serde(default)]
+    pub last_built: Option<SystemTime>,
+    pub modified: Option<SystemTime>,
+    pub target_modified: Option<SystemTime>,
+}
+
+impl Index {
+    /// Returns the location of the index file in the user directories
+    pub fn path(dirs: &UserDirs) -> PathBuf {
+        dirs.cache.join("index.json")
+    }
+
+    /// Loads the index from the given file, returning an empty index if it does not exist
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .context(format!("Failed to read index file: {}", path.display()))?;
+        serde_json::from_str(&content).or_else(|error| {
+            log::warn!("Ignoring corrupt index file {}: {error}", path.display());
+            Ok(Self::default())
+        })
+    }
+
+    /// Writes the index to the given file, creating parent directories as needed
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context(format!(
+                "Failed to create cache directory: {}",
+                parent.display()
+            ))?;
+        }
+        let content = serde_json::to_string(self).context("Failed to serialize index")?;
+        fs::write(path, content).context(format!("Failed to write index file: {}", path.display()))
+    }
+
+    pub fn root(&self, path: &Path) -> Option<&IndexedRoot> {
+        self.roots.iter().find(|root| root.path == path)
+    }
+
+    /// Returns the entry for the given root, replacing it if it was indexed with other settings
+    pub fn root_mut(&mut self, path: &Path, settings: DiscoverySettings) -> &mut IndexedRoot {
+        self.roots
+            .retain(|root| root.path != path || root.settings == settings);
+        let position = if let Some(position) = self.roots.iter().position(|r| r.path == path) {
+            position
+        } else {
+            self.roots.push(IndexedRoot {
+                path: path.to_path_buf(),
+                settings,
+                dirs: vec![],
+                projects: vec![],
+            });
+            self.roots.len() - 1
+        };
+        &mut self.roots[position]
+    }
+}
+
+impl IndexedRoot {
+    /// Returns `true` if no walked directory or its ignore files changed since the root
+    /// was indexed, so the project list is still complete
+    pub fn is_fresh(&self, root: &Path, settings: DiscoverySettings) -> bool {
+        self.settings == settings
+            && !self.dirs.is_empty()
+            && self.dirs.iter().all(|dir| {
+                let path = root.join(&dir.path);
+                modified(&path) == dir.modified && ignore_hash(&path) == dir.ignore_hash
+            })
+    }
+
+    pub fn project(&self, relative_path: &Path) -> Option<&IndexedProject> {
+        self.projects.iter().find(|p| p.path == relative_path)
+    }
+
+    /// Records the directories of a fresh walk and drops projects that were not found again
+    pub fn set_dirs(&mut self, root: &Path, dirs: &[PathBuf], candidates: &[PathBuf]) {
+        self.dirs = dirs
+            .iter()
+            .map(|dir| IndexedDir {
+                path: dir.strip_prefix(root).unwrap_or(dir).to_path_buf(),
+                modified: modified(dir),
+                ignore_hash: ignore_hash(dir),
+            })
+            .collect();
+        self.projects.retain(|project| {
+            project.path.as_os_str().is_empty() || candidates.contains(&root.join(&project.path))
+        });
+    }
+
+    /// Inserts or replaces the given project entries
+    pub fn upsert(&mut self, projects: Vec<IndexedProject>) {
+        for project in projects {
+            self.projects.retain(|p| p.path != project.path);
+            self.projects.push(project);
+        }
+    }
+}
+
+impl IndexedProject {
+    /// Creates an entry from a freshly sized project,
+    /// using the mtimes captured before the size was calculated
+    pub fn new(
+        relative_path: &Path,
+        target_dir: PathBuf,
+        project: &Project,
+        size_mode: SizeMode,
+        modified: Option<SystemTime>,
+        target_modified: Option<SystemTime>,
+    ) -> Self {
+        Self {
+            path: relative_path.to_path_buf(),
+            name: project.name.clone(),
+            id: project.id.clone(),
+            version: project.version.clone(),
+            target_dir,
+            size: project.apparent_size,
+            disk_size: project.disk_size,
+            size_errors: project.size_errors,
+            linked: project.linked.clone(),
+            hardlinked: project.hardlinked,
+            size_mode,
+            last_built: project.last_built,
+            modified,
+            target_modified,
+        }
+    }
+
+    /// Returns `true` if neither the project nor its target directory changed
+    /// and the entry has the hard link sizes for the given size mode.
+    /// Files added further down the target directory are only noticed by the size cache.
+    pub fn is_fresh(&self, project_path: &Path, size_mode: SizeMode) -> bool {
+        (self.linked.is_empty() || self.size_mode == size_mode)
+            && modified(project_path) == self.modified
+            && modified(&self.target_dir) == self.target_modified
+    }
+
+    pub fn to_project(&self, project_path: &Path, size_mode: SizeMode) -> Project {
+        let size = match size_mode {
+            SizeMode::Apparent => self.size,
+            SizeMode::Disk => self.disk_size,
+        };
+        let mut project = Project::new(&self.name, project_path, size);
+        project.apparent_size = self.size;
+        project.disk_size = self.disk_size;
+        project.id.clone_from(&self.id);
+        project.version.clone_from(&self.version);
+        project.size_errors = self.size_errors;
+        project.linked.clone_from(&self.linked);
+        project.hardlinked = self.hardlinked;
+        project.cached = true;
+        project.last_built = self.last_built;
+        project
+    }
+}
+
+/// Returns the modification time of a path, or `None` if it does not exist
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Hashes the names and contents of the ignore files in a directory, or `None` if it has none.
+/// The hash is stored in the index, so it uses FNV-1a, which unlike the hasher of the
+/// standard library does not change between Rust releases.
+pub fn ignore_hash(dir: &Path) -> Option<u64> {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    let mut hash = OFFSET_BASIS;
+    let mut found = false;
+    for name in IGNORE_FILES {
+        if let Ok(content) = fs::read(dir.join(name)) {
+            // The length separates the name from the content
+            let length = u64::try_from(content.len()).unwrap_or(u64::MAX);
+            for byte in name.bytes().chain(length.to_le_bytes()).chain(content) {
+                hash = (hash ^ u64::from(byte)).wrapping_mul(PRIME);
+            }
+            found = true;
+        }
+    }
+    found.then_some(hash)
+}
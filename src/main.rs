@@ -4,13 +4,23 @@ use cli::Commands;
 use log::LevelFilter;
 use simplelog::{ColorChoice, ConfigBuilder, TerminalMode};
 use std::{env, process::exit};
+use utility::UserDirs;
 
+mod cache;
 mod cli;
 mod commands;
+mod config;
 mod data;
 mod extensions;
+mod filter;
+mod git;
+mod history;
+mod index;
+mod manifest;
+mod sqlite;
 #[cfg(test)]
 mod test;
+mod toolchain;
 mod utility;
 
 fn main() {
@@ -26,7 +36,7 @@ fn main() {
 fn real_main() -> anyhow::Result<()> {
     initialize_logger()?;
     let args = Commands::parse_from(env::args().filter(|a| a != "wash"));
-    args.run()
+    args.run(&UserDirs::from_env()?)
 }
 
 fn initialize_logger() -> anyhow::Result<()> {
@@ -39,7 +49,7 @@ fn initialize_logger() -> anyhow::Result<()> {
         #[cfg(not(debug_assertions))]
         LevelFilter::Info,
         config,
-        TerminalMode::Mixed,
+        TerminalMode::Stderr,
         ColorChoice::Auto,
     )
     .context("Failed to initialize logger")
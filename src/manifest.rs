@@ -0,0 +1,89 @@
+use std::{fs, path::Path};
+
+use anyhow::Context as _;
+use serde::Deserialize;
+
+/// The parts of a `Cargo.toml` cargo-wash cares about
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Manifest {
+    pub package: Option<Package>,
+    pub workspace: Option<Workspace>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Package {
+    pub name: String,
+    pub version: Option<Inheritable>,
+    #[serde(rename = "rust-version")]
+    pub rust_version: Option<Inheritable>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Workspace {
+    pub package: Option<WorkspacePackage>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct WorkspacePackage {
+    pub version: Option<String>,
+    #[serde(rename = "rust-version")]
+    pub rust_version: Option<String>,
+}
+
+/// A field that is either set directly or inherited with `field.workspace = true`
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Inheritable {
+    Value(String),
+    Workspace { workspace: bool },
+}
+
+impl Manifest {
+    /// Reads and parses the `Cargo.toml` in the given project directory
+    pub fn read(project: &Path) -> anyhow::Result<Self> {
+        let path = project.join("Cargo.toml");
+        let content = fs::read_to_string(&path)
+            .context(format!("Failed to read manifest: {}", path.display()))?;
+        toml::from_str(&content).context(format!("Failed to parse manifest: {}", path.display()))
+    }
+
+    /// Returns the package name, or `None` for virtual workspaces
+    pub fn name(&self) -> Option<&str> {
+        self.package.as_ref().map(|p| p.name.as_str())
+    }
+
+    /// Returns the package version, resolving `version.workspace = true`
+    /// if the workspace is defined in the same manifest
+    pub fn version(&self) -> Option<&str> {
+        let package = self.package.as_ref()?;
+        self.inherit(package.version.as_ref()?, |workspace| {
+            workspace.version.as_deref()
+        })
+    }
+
+    /// Returns the minimum supported Rust version, resolving `rust-version.workspace = true`
+    /// if the workspace is defined in the same manifest
+    pub fn rust_version(&self) -> Option<&str> {
+        let package = self.package.as_ref()?;
+        self.inherit(package.rust_version.as_ref()?, |workspace| {
+            workspace.rust_version.as_deref()
+        })
+    }
+
+    fn inherit<'a>(
+        &'a self,
+        field: &'a Inheritable,
+        from_workspace: impl FnOnce(&'a WorkspacePackage) -> Option<&'a str>,
+    ) -> Option<&'a str> {
+        match field {
+            Inheritable::Value(value) => Some(value),
+            Inheritable::Workspace { workspace: true } => {
+                from_workspace(self.workspace.as_ref()?.package.as_ref()?)
+            }
+            Inheritable::Workspace { workspace: false } => None,
+        }
+    }
+}
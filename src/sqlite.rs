@@ -0,0 +1,265 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use anyhow::Context as _;
+
+/// The value of a column
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    pub const fn as_integer(&self) -> Option<i64> {
+        match self {
+            Self::Integer(value) => Some(*value),
+            Self::Null | Self::Real(_) | Self::Text(_) | Self::Blob(_) => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(value) => Some(value),
+            Self::Null | Self::Integer(_) | Self::Real(_) | Self::Blob(_) => None,
+        }
+    }
+}
+
+/// A row of a table with its rowid. Columns declared as `INTEGER PRIMARY KEY`
+/// are stored as `Null`, their value is the rowid.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Row {
+    pub rowid: i64,
+    pub values: Vec<Value>,
+}
+
+impl Row {
+    pub fn get(&self, column: usize) -> &Value {
+        self.values.get(column).unwrap_or(&Value::Null)
+    }
+}
+
+/// A minimal read-only reader for `SQLite` database files. It only lists the rows
+/// of rowid tables, which is all that is needed to read the global cache tracker of cargo.
+/// Every offset read from the file is checked, a corrupt file is an error and never a panic.
+pub struct Database {
+    data: Vec<u8>,
+    page_size: usize,
+    /// Page size without the bytes reserved at the end of every page
+    usable_size: usize,
+}
+
+impl Database {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let data =
+            fs::read(path).context(format!("Failed to read database: {}", path.display()))?;
+        Self::from_bytes(data).context(format!("Failed to read database: {}", path.display()))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            data.starts_with(b"SQLite format 3\0") && data.len() >= 100,
+            "Not an SQLite database"
+        );
+        let page_size = match u16::from_be_bytes([data[16], data[17]]) {
+            1 => 65536,
+            size => usize::from(size),
+        };
+        anyhow::ensure!(
+            page_size.is_power_of_two() && page_size >= 512,
+            "Invalid page size"
+        );
+        anyhow::ensure!(
+            data[56..60] == [0, 0, 0, 1],
+            "Only UTF-8 databases are supported"
+        );
+        let usable_size = page_size.saturating_sub(usize::from(data[20]));
+        anyhow::ensure!(usable_size >= 480, "Invalid page size");
+        Ok(Self {
+            data,
+            page_size,
+            usable_size,
+        })
+    }
+
+    /// Reads all rows of a table, ordered by rowid
+    pub fn rows(&self, table: &str) -> anyhow::Result<Vec<Row>> {
+        // The schema table lists every table with its root page: type, name, tbl_name, rootpage, sql
+        let root = self
+            .table_rows(1)?
+            .into_iter()
+            .find(|row| {
+                row.get(0).as_text() == Some("table") && row.get(1).as_text() == Some(table)
+            })
+            .and_then(|row| row.get(3).as_integer())
+            .context(format!("No table {table} in the database"))?;
+        self.table_rows(usize::try_from(root).context("Invalid root page")?)
+    }
+
+    /// Walks the b-tree of a table starting at its root page
+    fn table_rows(&self, root: usize) -> anyhow::Result<Vec<Row>> {
+        let mut rows = vec![];
+        let mut visited = HashSet::new();
+        let mut pending = vec![root];
+        while let Some(page) = pending.pop() {
+            anyhow::ensure!(visited.insert(page), "Loop in the b-tree at page {page}");
+            let start = self.page_start(page)?;
+            // The first page starts with the database header
+            let header = if page == 1 { start + 100 } else { start };
+            let kind = *self.bytes(header, 1)?.first().context("Empty page")?;
+            let cells = usize::from(self.u16(header + 3)?);
+            match kind {
+                // Interior page, the children are visited in order through the stack
+                0x05 => {
+                    pending.push(self.u32(header + 8)?);
+                    for cell in (0..cells).rev() {
+                        let offset = usize::from(self.u16(header + 12 + cell * 2)?);
+                        pending.push(self.u32(start + offset)?);
+                    }
+                }
+                // Leaf page
+                0x0d => {
+                    for cell in 0..cells {
+                        let offset = usize::from(self.u16(header + 8 + cell * 2)?);
+                        rows.push(self.leaf_cell(start + offset)?);
+                    }
+                }
+                _ => anyhow::bail!("Page {page} is no table page"),
+            }
+        }
+        Ok(rows)
+    }
+
+    fn leaf_cell(&self, position: usize) -> anyhow::Result<Row> {
+        let (payload_size, length) = self.varint(position)?;
+        let (rowid, rowid_length) = self.varint(position + length)?;
+        let payload_size = usize::try_from(payload_size).context("Invalid payload size")?;
+        let payload = self.payload(position + length + rowid_length, payload_size)?;
+        Ok(Row {
+            rowid: rowid.cast_signed(),
+            values: record(&payload)?,
+        })
+    }
+
+    /// Reads a payload, following the overflow pages if it does not fit into the page
+    fn payload(&self, position: usize, size: usize) -> anyhow::Result<Vec<u8>> {
+        // Overflow pages may form a loop, the size bounds how often they are followed
+        anyhow::ensure!(size <= self.data.len(), "Invalid payload size");
+        let max_local = self.usable_size - 35;
+        if size <= max_local {
+            return Ok(self.bytes(position, size)?.to_vec());
+        }
+        let min_local = (self.usable_size - 12) * 32 / 255 - 23;
+        let local = min_local + (size - min_local) % (self.usable_size - 4);
+        let local = if local <= max_local { local } else { min_local };
+
+        let mut payload = self.bytes(position, local)?.to_vec();
+        let mut next = self.u32(position + local)?;
+        while payload.len() < size {
+            anyhow::ensure!(next != 0, "Overflow pages end early");
+            let start = self.page_start(next)?;
+            let chunk = (size - payload.len()).min(self.usable_size - 4);
+            payload.extend_from_slice(self.bytes(start + 4, chunk)?);
+            next = self.u32(start)?;
+        }
+        Ok(payload)
+    }
+
+    fn page_start(&self, page: usize) -> anyhow::Result<usize> {
+        page.checked_sub(1)
+            .and_then(|index| index.checked_mul(self.page_size))
+            .filter(|start| *start < self.data.len())
+            .context(format!("Invalid page number {page}"))
+    }
+
+    fn bytes(&self, start: usize, length: usize) -> anyhow::Result<&[u8]> {
+        start
+            .checked_add(length)
+            .and_then(|end| self.data.get(start..end))
+            .context("Unexpected end of the database")
+    }
+
+    fn u16(&self, position: usize) -> anyhow::Result<u16> {
+        let bytes = self.bytes(position, 2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&self, position: usize) -> anyhow::Result<usize> {
+        let bytes = self.bytes(position, 4)?;
+        usize::try_from(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .context("Invalid page number")
+    }
+
+    fn varint(&self, position: usize) -> anyhow::Result<(u64, usize)> {
+        varint(self.data.get(position..).unwrap_or_default())
+    }
+}
+
+/// Reads a big-endian variable-length integer of up to 9 bytes, returning it with its length
+fn varint(bytes: &[u8]) -> anyhow::Result<(u64, usize)> {
+    let mut value = 0_u64;
+    for (index, byte) in bytes.iter().take(9).enumerate() {
+        if index == 8 {
+            return Ok(((value << 8) | u64::from(*byte), 9));
+        }
+        value = (value << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Ok((value, index + 1));
+        }
+    }
+    anyhow::bail!("Unexpected end of a varint")
+}
+
+/// Decodes a record, a header with the serial type of every column followed by the values
+fn record(payload: &[u8]) -> anyhow::Result<Vec<Value>> {
+    let (header_size, mut position) = varint(payload)?;
+    let header_size = usize::try_from(header_size).context("Invalid record header")?;
+    let mut types = vec![];
+    while position < header_size {
+        let (serial_type, length) = varint(payload.get(position..).unwrap_or_default())?;
+        types.push(serial_type);
+        position += length;
+    }
+
+    let mut body = payload
+        .get(header_size..)
+        .context("Invalid record header")?;
+    let mut values = vec![];
+    for serial_type in types {
+        let length = match serial_type {
+            0 | 8 | 9 | 10 | 11 => 0,
+            1..=4 => usize::try_from(serial_type)?,
+            5 => 6,
+            6 | 7 => 8,
+            _ => usize::try_from((serial_type - 12) / 2)?,
+        };
+        let (bytes, rest) = body
+            .split_at_checked(length)
+            .context("Unexpected end of a record")?;
+        body = rest;
+        values.push(match serial_type {
+            0 | 10 | 11 => Value::Null,
+            1..=6 => {
+                // Sign-extend the big-endian integer
+                let mut value = if bytes.first().is_some_and(|b| b & 0x80 != 0) {
+                    -1_i64
+                } else {
+                    0
+                };
+                for byte in bytes {
+                    value = (value << 8) | i64::from(*byte);
+                }
+                Value::Integer(value)
+            }
+            7 => Value::Real(f64::from_bits(u64::from_be_bytes(bytes.try_into()?))),
+            8 => Value::Integer(0),
+            9 => Value::Integer(1),
+            _ if serial_type % 2 == 0 => Value::Blob(bytes.to_vec()),
+            _ => Value::Text(String::from_utf8_lossy(bytes).to_string()),
+        });
+    }
+    Ok(values)
+}
@@ -1,9 +1,11 @@
-#![expect(clippy::float_cmp, clippy::similar_names, reason = "Tests")]
+#![expect(clippy::float_cmp, reason = "Tests")]
 use std::{
+    ffi::OsString,
     fs,
     path::{Path, PathBuf},
     process::Command,
     str,
+    time::{Duration, SystemTime},
 };
 
 use anyhow::Context;
@@ -11,14 +13,29 @@ use clap::{ColorChoice, CommandFactory as _};
 use insta::_macro_support;
 
 use crate::{
+    cache::{CACHE_VERSION, SizeCache},
     cli::{
-        self, Commands,
-        opts::{Options, OptionsTrait as _},
+        self, Commands, RootsCommand,
+        opts::{
+            HomeCommand, HomeOptions, Options, OptionsTrait as _, StatsFormat, StatsOptions,
+            StatsSort, ToolchainsOptions,
+        },
     },
-    commands::{clean, executor, total_size_of_projects},
-    data::{self, Project, Size, SizeUnit},
+    commands::{
+        bins, breakdown, clean, crates,
+        diff::{self, Scope},
+        executor, home, roots, stats, toolchains, total_size_of_projects,
+    },
+    config::Config,
+    data::{FolderSize, Project, Size, SizeMode, SizeUnit, Units},
     extensions::PathBufExt as _,
-    utility,
+    filter::ProjectFilter,
+    git::{self, GitStatus},
+    history::{self, Since, Snapshot},
+    index::{self, Index},
+    sqlite::{Database, Value},
+    toolchain::{self, Toolchain},
+    utility::{self, UserDirs},
 };
 
 #[test]
@@ -75,10 +92,18 @@ fn test_check_project() {
         .find(|p| p.join("Cargo.toml").exists())
         .unwrap();
 
-    let res = utility::get_project(&project_root.to_path_buf(), None)
-        .unwrap()
-        .unwrap();
-    assert!(res.size > data::Size::to_size(0));
+    let res = utility::get_project(
+        &project_root.to_path_buf(),
+        project_root,
+        project_root,
+        &ProjectFilter::default(),
+        SizeMode::Apparent,
+        &SizeCache::default(),
+        None,
+    )
+    .unwrap()
+    .unwrap();
+    assert!(res.size > 0);
     assert!(res.name.contains("cargo-wash"));
     assert!(
         res.path
@@ -109,36 +134,1384 @@ fn test_cli_snapshot() {
     });
 }
 
+/// Projects with fixed paths and sizes for the stats output snapshots
+fn stats_projects() -> Vec<Project> {
+    let mut app = Project::new("app", "/projects/app", 1536);
+    app.version = Some("1.0.0".to_owned());
+    app.cached = true;
+    app.toolchain = Some(Toolchain {
+        pinned: Some("1.80".to_owned()),
+        rust_version: Some("1.75".to_owned()),
+        built_with: vec!["1.79.0".to_owned()],
+        compilers: 2,
+        mismatch: Some(true),
+    });
+    app.git = Some(GitStatus {
+        branch: Some("main".to_owned()),
+        dirty: Some(false),
+        ahead: Some(0),
+        behind: Some(3),
+    });
+    let mut lib = Project::new("lib", "/projects/tools/lib, old", 3 * 1024 * 1024);
+    lib.id = "tools/lib".to_owned();
+    lib.hardlinked = 1024;
+    lib.size_errors = 2;
+    vec![lib, app]
+}
+
+#[test]
+fn test_stats_formats() {
+    for (format, name) in [
+        (StatsFormat::Table, "stats-table"),
+        (StatsFormat::Json, "stats-json"),
+        (StatsFormat::Csv, "stats-csv"),
+        (StatsFormat::Markdown, "stats-markdown"),
+    ] {
+        let options = StatsOptions {
+            format,
+            long: true,
+            ..Default::default()
+        };
+        let output = stats::render(&stats_projects(), &options).unwrap();
+        insta::with_settings!({
+            snapshot_path => "../tests/snapshots",
+        }, {
+            insta::assert_snapshot!(name, output);
+        });
+    }
+}
+
+#[test]
+fn test_stats_sort_top() {
+    let mut small = Project::new("Small", "/projects/small", 10);
+    small.last_built = Some(SystemTime::UNIX_EPOCH);
+    let mut projects = stats_projects();
+    projects.push(small);
+
+    let options = StatsOptions {
+        format: StatsFormat::Markdown,
+        sort: StatsSort::Size,
+        top: Some(2),
+        ..Default::default()
+    };
+    let output = stats::render(&projects, &options).unwrap();
+    insta::with_settings!({
+        snapshot_path => "../tests/snapshots",
+    }, {
+        insta::assert_snapshot!("stats-top", output);
+    });
+
+    let options = StatsOptions {
+        format: StatsFormat::Csv,
+        sort: StatsSort::Age,
+        reverse: true,
+        ..Default::default()
+    };
+    let output = stats::render(&projects, &options).unwrap();
+    let names: Vec<_> = output
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split(',').next())
+        .collect();
+    assert_eq!(names, ["lib", "app", "Small"]);
+}
+
+#[test]
+fn test_stats_tree() {
+    let mut projects = stats_projects();
+    for (id, size) in [
+        ("tools/cli", 512 * 1024),
+        ("tools/nested/a", 100),
+        ("tools/nested/b", 200),
+        ("games/snake", 2 * 1024 * 1024),
+    ] {
+        let mut project = Project::new(id.rsplit('/').next().unwrap(), "/projects", size);
+        project.id = id.to_owned();
+        projects.push(project);
+    }
+    projects[0].name = "lib-core".to_owned();
+
+    for (depth, name) in [(None, "stats-tree"), (Some(1), "stats-tree-depth")] {
+        let options = StatsOptions {
+            tree: true,
+            depth,
+            ..Default::default()
+        };
+        let output = stats::render(&projects, &options).unwrap();
+        insta::with_settings!({
+            snapshot_path => "../tests/snapshots",
+        }, {
+            insta::assert_snapshot!(name, output);
+        });
+    }
+}
+
+#[test]
+fn test_stats_tree_roots() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let roots = ["work", "other/work"].map(|root| tmp_dir.path().join(root));
+    for root in &roots {
+        fs::create_dir_all(root.join("tools/core")).unwrap();
+        fs::write(root.join("tools/core/Cargo.toml"), "").unwrap();
+    }
+    let tree = |paths: &[PathBuf]| {
+        let options = Options {
+            path: paths.to_vec(),
+            no_cache: true,
+            max_depth: 2,
+            ..Default::default()
+        };
+        let projects = options
+            .scan(&UserDirs::in_dir(tmp_dir.path()), false)
+            .unwrap();
+        let options = StatsOptions {
+            tree: true,
+            ..Default::default()
+        };
+        stats::render(&projects, &options)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let (_, tree) = line.split_once('%').unwrap();
+                tree.strip_prefix("  ").unwrap().to_owned()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        tree(&roots[..1]),
+        [". (1 project)", "└── tools (1 project)", "    └── core"]
+    );
+    // The ids are full paths, as both roots are named `work`
+    let mut expected = vec![". (2 projects)".to_owned()];
+    // Both roots are equally large, so they are sorted by their path
+    for (position, root) in [&roots[1], &roots[0]].into_iter().enumerate() {
+        let (branch, indent) = if position == 0 {
+            ("├── ", "│   ")
+        } else {
+            ("└── ", "    ")
+        };
+        expected.push(format!("{branch}{} (1 project)", root.display()));
+        expected.push(format!("{indent}└── tools (1 project)"));
+        expected.push(format!("{indent}    └── core"));
+    }
+    assert_eq!(tree(&roots), expected);
+}
+
+#[test]
+fn test_stats_breakdown() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let target = tmp_dir.path().join("app/target");
+    for (file, size) in [
+        ("CACHEDIR.TAG", 10),
+        ("debug/app", 4000),
+        ("debug/deps/app-1234", 3000),
+        ("debug/deps/libserde-5678.rlib", 2000),
+        ("debug/build/serde-abcd/out/lib.rs", 500),
+        ("debug/incremental/app-1/s-1/query-cache.bin", 1500),
+        ("debug/.fingerprint/app-1234/output", 20),
+        ("release/deps/app-9999", 2500),
+        ("wasm32-unknown-unknown/debug/deps/app-4321.wasm", 800),
+        ("doc/app/index.html", 300),
+    ] {
+        let path = target.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![0; size]).unwrap();
+    }
+
+    let project = Project::new("app", tmp_dir.path().join("app"), 0);
+    let options = StatsOptions {
+        project: Some("app".to_owned()),
+        breakdown: true,
+        top: Some(3),
+        options: Options {
+            units: Units::Bytes,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let output = breakdown::render(&project, &options);
+    insta::with_settings!({
+        snapshot_path => "../tests/snapshots",
+    }, {
+        insta::assert_snapshot!("stats-breakdown", output);
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn test_stats_breakdown_links() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let target = tmp_dir.path().join("app/target");
+    fs::create_dir_all(target.join("debug/deps")).unwrap();
+    fs::write(target.join("debug/deps/app-1234"), vec![1; 3000]).unwrap();
+    fs::hard_link(target.join("debug/deps/app-1234"), target.join("debug/app")).unwrap();
+    fs::write(target.join("debug/deps/libserde-5678.rlib"), vec![1; 5000]).unwrap();
+    // A sparse file is large, but takes no space on disk
+    fs::File::create(target.join("debug/sparse"))
+        .unwrap()
+        .set_len(1024 * 1024)
+        .unwrap();
+
+    let project = Project::new("app", tmp_dir.path().join("app"), 0);
+    let largest = |size_mode: SizeMode| {
+        let options = StatsOptions {
+            breakdown: true,
+            top: Some(2),
+            options: Options {
+                size_mode,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let output = breakdown::render(&project, &options);
+        let table = &output[output.find("Largest files").unwrap()..];
+        [
+            "sparse",
+            "libserde-5678.rlib",
+            "debug/app (+1 hard link)",
+            "app-1234",
+        ]
+        .into_iter()
+        .filter(|name| table.contains(name))
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        largest(SizeMode::Apparent),
+        ["sparse", "libserde-5678.rlib"]
+    );
+    assert_eq!(
+        largest(SizeMode::Disk),
+        ["libserde-5678.rlib", "debug/app (+1 hard link)"]
+    );
+}
+
+#[test]
+fn test_stats_crates() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let registry = "/home/user/.cargo/registry/src/index.crates.io-1949cf8c6b5b557f";
+    let mut projects = vec![];
+    for (project, files) in [
+        (
+            "a",
+            vec![
+                ("debug/deps/libserde-0a1b.rlib", 3000),
+                ("debug/deps/libserde-0a1b.rmeta", 1000),
+                ("debug/.fingerprint/serde-9f8e/lib-serde", 20),
+                ("debug/deps/libproc_macro2-1111.rlib", 500),
+                ("debug/deps/a-2222", 700),
+                ("debug/.fingerprint/a-3333/bin-a", 20),
+                ("debug/a", 700),
+            ],
+        ),
+        (
+            "b",
+            vec![
+                ("debug/deps/libserde-3c4d.rlib", 2800),
+                ("release/deps/libserde-5e6f.rlib", 2000),
+                ("debug/deps/libsha_1-7777.rlib", 100),
+            ],
+        ),
+    ] {
+        let target = tmp_dir.path().join(project).join("target");
+        for (file, size) in files {
+            let path = target.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, vec![0; size]).unwrap();
+        }
+        projects.push(Project::new(project, tmp_dir.path().join(project), 0));
+    }
+
+    let target = tmp_dir.path().join("a/target/debug/deps");
+    for (file, source) in [
+        ("serde-0a1b.d", "serde-1.0.200/src/lib.rs"),
+        ("proc_macro2-1111.d", "proc-macro2-1.0.80/src/lib.rs"),
+    ] {
+        fs::write(
+            target.join(file),
+            format!("{}: {registry}/{source}\n", target.join(file).display()),
+        )
+        .unwrap();
+    }
+    // The crates of the workspace are no dependencies
+    fs::write(
+        target.join("a-2222.d"),
+        format!("{}: /work/a/src/main.rs\n", target.join("a-2222").display()),
+    )
+    .unwrap();
+    let target = tmp_dir.path().join("b/target");
+    for (file, source) in [
+        ("debug/deps/serde-3c4d.d", "serde-1.0.200/src/lib.rs"),
+        ("release/deps/serde-5e6f.d", "serde-1.0.199/src/lib.rs"),
+        ("debug/deps/sha_1-7777.d", "sha-1-0.10.1/src/lib.rs"),
+    ] {
+        fs::write(target.join(file), format!("out: {registry}/{source}\n")).unwrap();
+    }
+
+    let options = StatsOptions {
+        crates: true,
+        options: Options {
+            units: Units::Bytes,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let output = crates::render(&projects, &options);
+    insta::with_settings!({
+        snapshot_path => "../tests/snapshots",
+    }, {
+        insta::assert_snapshot!("stats-crates", output);
+    });
+}
+
+#[test]
+fn test_history_diff() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let history_path = tmp_dir.path().join("history.jsonl");
+    let now = SystemTime::now();
+    let day = Duration::from_hours(24);
+
+    // A project that still exists was left out of the scan, e.g. by --max-depth
+    let existing = tmp_dir.path().join("existing");
+    fs::create_dir_all(&existing).unwrap();
+    fs::write(existing.join("Cargo.toml"), "").unwrap();
+
+    let mut old = stats_projects();
+    old.push(Project::new("gone", "/projects/gone", 4096));
+    old.push(Project::new("skipped", "/projects/skipped", 8192));
+    old.push(Project::new("other", "/elsewhere/other", 8192));
+    old.push(Project::new("existing", &existing, 8192));
+    for (age, projects) in [(10, &old[..]), (1, &old[1..3])] {
+        let snapshot = Snapshot::new(projects, SizeMode::Apparent, now - day * age);
+        history::append(&history_path, &snapshot).unwrap();
+    }
+    fs::write(
+        &history_path,
+        fs::read_to_string(&history_path).unwrap() + "not json\n",
+    )
+    .unwrap();
+
+    let snapshots = history::load(&history_path).unwrap();
+    assert_eq!(snapshots.len(), 2);
+    let latest = history::find(&snapshots, Since::Latest, now).unwrap();
+    assert_eq!(latest.projects.len(), 2);
+    let week = history::find(&snapshots, Since::parse("7d").unwrap(), now).unwrap();
+    assert_eq!(week.projects.len(), 6);
+    assert!(history::find(&snapshots, Since::parse("2w").unwrap(), now).is_none());
+
+    let mut projects = stats_projects();
+    projects[0].size += 1024 * 1024;
+    projects.push(Project::new("new", "/projects/new", 100));
+    let options = StatsOptions::default();
+    let roots = [PathBuf::from("/projects"), tmp_dir.path().to_path_buf()];
+    let filter = ProjectFilter::new(&[], &["skipped".to_owned()], &[]).unwrap();
+    let output = diff::render(&projects, week, &Scope::new(&roots, filter), &options);
+    insta::with_settings!({
+        snapshot_path => "../tests/snapshots",
+    }, {
+        insta::assert_snapshot!("stats-diff", output);
+    });
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(
+        utility::parse_duration("30d").unwrap(),
+        Duration::from_hours(30 * 24)
+    );
+    assert_eq!(
+        utility::parse_duration("12h").unwrap(),
+        Duration::from_hours(12)
+    );
+    utility::parse_duration("12").unwrap_err();
+    utility::parse_duration("d").unwrap_err();
+    assert_eq!(
+        utility::format_duration(Duration::from_secs(3 * 24 * 60 * 60 + 5)),
+        "3d"
+    );
+}
+
+#[test]
+fn test_stale() {
+    let now = SystemTime::now();
+    assert_eq!(utility::format_age(None, now), "never");
+    assert_eq!(utility::format_age(Some(now), now), "just now");
+    let three_days = now.checked_sub(Duration::from_hours(3 * 24));
+    assert_eq!(utility::format_age(three_days, now), "3 days ago");
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let project = tmp_dir.path().join("app");
+    fs::create_dir_all(project.join("target/debug")).unwrap();
+    fs::write(project.join("Cargo.toml"), "").unwrap();
+    fs::write(project.join("target/debug/app"), [0; 100]).unwrap();
+
+    let scan = |stale: Option<Duration>| {
+        let options = Options {
+            path: vec![tmp_dir.path().to_path_buf()],
+            no_cache: true,
+            stale,
+            ..Default::default()
+        };
+        options
+            .scan(&UserDirs::in_dir(tmp_dir.path()), false)
+            .unwrap()
+    };
+    let projects = scan(None);
+    assert_eq!(projects.len(), 1);
+    assert!(projects[0].last_built.is_some());
+    // The project was just built, so it is not stale
+    assert!(scan(Some(Duration::from_hours(24))).is_empty());
+}
+
+#[test]
+fn test_toolchain() {
+    assert_eq!(
+        toolchain::parse_toolchain_file("[toolchain]\nchannel = \"1.80.0\"\n").as_deref(),
+        Some("1.80.0")
+    );
+    assert_eq!(
+        toolchain::parse_toolchain_file("nightly-2024-07-01\n").as_deref(),
+        Some("nightly-2024-07-01")
+    );
+    assert_eq!(toolchain::matches_channel("1.80", "1.80.1"), Some(true));
+    assert_eq!(toolchain::matches_channel("1.80.0", "1.81.0"), Some(false));
+    assert_eq!(toolchain::matches_channel("1.8", "1.80.0"), Some(false));
+    assert_eq!(
+        toolchain::matches_channel("stable", "1.82.0-nightly"),
+        Some(false)
+    );
+    assert_eq!(
+        toolchain::matches_channel("nightly-2024-07-01", "1.81.0-nightly"),
+        Some(true)
+    );
+    assert_eq!(toolchain::matches_channel("my-toolchain", "1.80.0"), None);
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let project = tmp_dir.path().join("app");
+    for unit in ["serde-1a2b", "app-3c4d"] {
+        fs::create_dir_all(project.join("target/debug/.fingerprint").join(unit)).unwrap();
+    }
+    fs::write(
+        project.join("Cargo.toml"),
+        "[package]\nname = \"app\"\nrust-version = \"1.75\"\n",
+    )
+    .unwrap();
+    fs::write(
+        tmp_dir.path().join("rust-toolchain.toml"),
+        "[toolchain]\nchannel = \"1.80\"\n",
+    )
+    .unwrap();
+    fs::write(
+        project.join("target/.rustc_info.json"),
+        r#"{"rustc_fingerprint":1,"outputs":{"2":{"success":true,"status":"","code":0,"stdout":"rustc 1.80.1\nrelease: 1.80.1\n","stderr":""}}}"#,
+    )
+    .unwrap();
+    fs::write(
+        project.join("target/debug/.fingerprint/serde-1a2b/lib-serde.json"),
+        r#"{"rustc":11}"#,
+    )
+    .unwrap();
+    fs::write(
+        project.join("target/debug/.fingerprint/app-3c4d/bin-app.json"),
+        r#"{"rustc":11}"#,
+    )
+    .unwrap();
+
+    let read = Toolchain::read(&project);
+    assert_eq!(read.pinned.as_deref(), Some("1.80"));
+    assert_eq!(read.rust_version.as_deref(), Some("1.75"));
+    assert_eq!(read.built_with, ["1.80.1"]);
+    assert_eq!(read.mismatch, Some(false));
+
+    // An older compiler built some of the artifacts
+    fs::write(
+        project.join("target/debug/.fingerprint/serde-1a2b/lib-serde.json"),
+        r#"{"rustc":12}"#,
+    )
+    .unwrap();
+    // Left over from another compiler, which is not known
+    let read = Toolchain::read(&project);
+    assert_eq!(read.compilers, 2);
+    assert_eq!(read.mismatch, None);
+    assert_eq!(read.built_with_label(), "1.80.1, 1 other (unknown)");
+
+    fs::write(
+        project.join("target/debug/.fingerprint/serde-1a2b/lib-serde.json"),
+        r#"{"rustc":11}"#,
+    )
+    .unwrap();
+    let rustc_info = fs::read_to_string(project.join("target/.rustc_info.json")).unwrap();
+    fs::remove_file(project.join("target/.rustc_info.json")).unwrap();
+    assert_eq!(Toolchain::read(&project).mismatch, None);
+
+    // The latest build used another release
+    fs::write(
+        project.join("target/.rustc_info.json"),
+        rustc_info.replace("1.80.1", "1.79.0"),
+    )
+    .unwrap();
+    let read = Toolchain::read(&project);
+    assert_eq!(read.mismatch, Some(true));
+    assert_eq!(read.built_with_label(), "1.79.0 (mismatch)");
+}
+
+#[test]
+fn test_git() {
+    let mut status = GitStatus::default();
+    status.apply_porcelain(
+        "# branch.oid 1a2b\n# branch.head main\n# branch.upstream origin/main\n# branch.ab +2 -1\n? new.rs\n",
+    );
+    assert_eq!(status.dirty, Some(true));
+    assert_eq!((status.ahead, status.behind), (Some(2), Some(1)));
+    assert_eq!(status.upstream_label(), "2 ahead, 1 behind");
+    status.apply_porcelain("# branch.oid 1a2b\n# branch.head main\n");
+    assert_eq!(status.dirty, Some(false));
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let root = tmp_dir.path();
+    fs::create_dir_all(root.join("repo/.git")).unwrap();
+    fs::write(root.join("repo/.git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    fs::create_dir_all(root.join("repo/crates/app")).unwrap();
+    assert_eq!(
+        git::git_dir(&root.join("repo/crates/app")),
+        Some(root.join("repo/.git"))
+    );
+    // Worktrees and submodules point to their git directory
+    fs::create_dir_all(root.join("worktree")).unwrap();
+    fs::write(
+        root.join("worktree/.git"),
+        "gitdir: ../repo/.git/worktrees/worktree\n",
+    )
+    .unwrap();
+    assert_eq!(
+        git::git_dir(&root.join("worktree")),
+        Some(root.join("worktree/../repo/.git/worktrees/worktree"))
+    );
+
+    let output = stats::render(
+        &stats_projects(),
+        &StatsOptions {
+            git: true,
+            format: StatsFormat::Markdown,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    insta::with_settings!({
+        snapshot_path => "../tests/snapshots",
+    }, {
+        insta::assert_snapshot!("stats-git", output);
+    });
+}
+
+#[test]
+fn test_git_filters() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    for project in ["app", "lib"] {
+        fs::create_dir_all(tmp_dir.path().join(project)).unwrap();
+        fs::write(tmp_dir.path().join(project).join("Cargo.toml"), "").unwrap();
+    }
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(["-c", "user.name=wash", "-c", "user.email=wash@example.com"])
+            .args(args)
+            .current_dir(tmp_dir.path())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    };
+    git(&["init", "--quiet"]);
+    git(&["add", "."]);
+    git(&["commit", "--quiet", "-m", "init"]);
+    // Only the changes below a project make it dirty, not those elsewhere in the repository
+    fs::write(tmp_dir.path().join("lib/Cargo.toml"), "[package]").unwrap();
+
+    let scan = |clean: bool| {
+        let options = Options {
+            path: vec![tmp_dir.path().to_path_buf()],
+            no_cache: true,
+            only_clean_git: clean,
+            only_dirty_git: !clean,
+            ..Default::default()
+        };
+        let projects = options
+            .scan(&UserDirs::in_dir(tmp_dir.path()), false)
+            .unwrap();
+        projects.into_iter().map(|p| p.name).collect::<Vec<_>>()
+    };
+    assert_eq!(scan(true), ["app"]);
+    assert_eq!(scan(false), ["lib"]);
+}
+
+#[test]
+fn test_home_clean() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let home = tmp_dir.path();
+    let registry = "index.crates.io-1949cf8c6b5b557f";
+    let cache = home.join("registry/cache").join(registry);
+    let src = home.join("registry/src").join(registry).join("serde-1.0.0");
+    let checkout = home.join("git/checkouts/wgpu-1a2b/3c4d5e6");
+    for dir in [&cache, &src, &checkout, &home.join("git/db/wgpu-1a2b")] {
+        fs::create_dir_all(dir).unwrap();
+    }
+    fs::write(src.join("lib.rs"), [0; 10]).unwrap();
+    fs::write(checkout.join("lib.rs"), [0; 10]).unwrap();
+    fs::write(cache.join("serde-1.0.0.crate"), [0; 10]).unwrap();
+    let old = fs::File::create(cache.join("serde-0.9.0.crate")).unwrap();
+    old.set_modified(SystemTime::now() - Duration::from_hours(100 * 24))
+        .unwrap();
+
+    let clean = |options: HomeOptions| {
+        home::run(
+            &HomeOptions {
+                yes: true,
+                ..options
+            },
+            home,
+        )
+        .unwrap();
+    };
+    clean(HomeOptions {
+        clean_src: true,
+        clean_checkouts: true,
+        dry_run: true,
+        ..Default::default()
+    });
+    assert!(src.exists());
+
+    clean(HomeOptions {
+        clean_src: true,
+        clean_cache: true,
+        older_than: Some(Duration::from_hours(90 * 24)),
+        ..Default::default()
+    });
+    assert!(!home.join("registry/src").join(registry).exists());
+    assert!(cache.join("serde-1.0.0.crate").exists());
+    assert!(!cache.join("serde-0.9.0.crate").exists());
+    // Checkouts are only removed with --clean-checkouts
+    assert!(checkout.exists());
+}
+
+#[test]
+fn test_sqlite() {
+    let database = Database::open(Path::new("tests/fixtures/global-cache")).unwrap();
+    let registries = database.rows("registry_index").unwrap();
+    assert_eq!(registries.len(), 1);
+    // `INTEGER PRIMARY KEY` columns are stored as the rowid
+    assert_eq!(registries[0].rowid, 1);
+    assert_eq!(registries[0].get(0), &Value::Null);
+    assert_eq!(
+        registries[0].get(1).as_text(),
+        Some("index.crates.io-1949cf8c6b5b557f")
+    );
+
+    // Spread over several pages
+    let crates = database.rows("registry_crate").unwrap();
+    assert_eq!(crates.len(), 401);
+    assert_eq!(crates[399].get(1).as_text(), Some("crate399-1.0.0.crate"));
+    assert_eq!(crates[399].get(3).as_integer(), Some(1399));
+
+    // Stored on overflow pages
+    let git = database.rows("git_db").unwrap();
+    assert_eq!(git[0].get(1).as_text().map(str::len), Some(5000));
+
+    database.rows("missing").unwrap_err();
+    Database::from_bytes(b"not a database".to_vec())
+        .map(|_| ())
+        .unwrap_err();
+}
+
+#[test]
+fn test_sqlite_corrupt() {
+    let data = fs::read("tests/fixtures/global-cache").unwrap();
+    let tables = ["registry_index", "registry_crate", "registry_src", "git_db"];
+    // Reading a damaged file may fail, but must not panic or hang
+    let read = |data: Vec<u8>| {
+        if let Ok(database) = Database::from_bytes(data) {
+            for table in tables {
+                database.rows(table).ok();
+            }
+        }
+    };
+
+    for length in (0..data.len()).step_by(97) {
+        read(data[..length].to_vec());
+    }
+    let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+    let mut random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    for _ in 0..2000 {
+        let mut corrupt = data.clone();
+        for _ in 0..4 {
+            let position = usize::try_from(random()).unwrap() % corrupt.len();
+            corrupt[position] = random().to_le_bytes()[0];
+        }
+        read(corrupt);
+    }
+
+    // No page size and bytes reserved at the end of every page
+    let mut corrupt = data.clone();
+    corrupt[16..18].copy_from_slice(&[0, 0]);
+    corrupt[20] = 1;
+    Database::from_bytes(corrupt).map(|_| ()).unwrap_err();
+    // The first cell of the schema claims a payload larger than the file
+    let mut corrupt = data.clone();
+    let cell = usize::from(u16::from_be_bytes([corrupt[108], corrupt[109]]));
+    corrupt[cell..cell + 9].fill(0xff);
+    Database::from_bytes(corrupt)
+        .unwrap()
+        .rows("registry_crate")
+        .unwrap_err();
+
+    let mut truncated = data;
+    truncated.truncate(4096 * 3 + 100);
+    Database::from_bytes(truncated)
+        .unwrap()
+        .rows("registry_crate")
+        .unwrap_err();
+}
+
+#[test]
+fn test_home_gc() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let home = tmp_dir.path();
+    let registry = "index.crates.io-1949cf8c6b5b557f";
+    let cache = home.join("registry/cache").join(registry);
+    let src = home.join("registry/src").join(registry);
+    for dir in [&cache, &src.join("serde-1.0.0"), &src.join("old-1.0.0")] {
+        fs::create_dir_all(dir).unwrap();
+    }
+    for name in ["serde-1.0.0", "crate7-1.0.0", "untracked-1.0.0"] {
+        fs::write(cache.join(format!("{name}.crate")), [0; 10]).unwrap();
+    }
+
+    let gc = || {
+        let options = HomeOptions {
+            command: Some(HomeCommand::Gc {
+                max_age: Duration::from_hours(90 * 24),
+            }),
+            yes: true,
+            ..Default::default()
+        };
+        home::run(&options, home).unwrap();
+    };
+
+    // Without the tracker, the files were just used
+    gc();
+    assert!(cache.join("crate7-1.0.0.crate").exists());
+    assert!(src.join("old-1.0.0").exists());
+
+    fs::copy("tests/fixtures/global-cache", home.join(".global-cache")).unwrap();
+    // The tracker is not trusted while it has a journal
+    fs::write(home.join(".global-cache-journal"), [1]).unwrap();
+    gc();
+    assert!(cache.join("crate7-1.0.0.crate").exists());
+    fs::remove_file(home.join(".global-cache-journal")).unwrap();
+
+    // Nothing is removed while cargo holds the package cache
+    let lock = home::lock_package_cache(home).unwrap();
+    let options = HomeOptions {
+        command: Some(HomeCommand::Gc {
+            max_age: Duration::from_hours(90 * 24),
+        }),
+        yes: true,
+        ..Default::default()
+    };
+    home::run(&options, home).unwrap_err();
+    assert!(cache.join("crate7-1.0.0.crate").exists());
+    drop(lock);
+
+    gc();
+    assert!(cache.join("serde-1.0.0.crate").exists());
+    assert!(!cache.join("crate7-1.0.0.crate").exists());
+    // Unknown to the tracker and judged by its file times
+    assert!(cache.join("untracked-1.0.0.crate").exists());
+    assert!(src.join("serde-1.0.0").exists());
+    assert!(!src.join("old-1.0.0").exists());
+}
+
+#[test]
+fn test_home_bins() {
+    assert_eq!(
+        bins::split_package_id(
+            "ripgrep 14.1.0 (registry+https://github.com/rust-lang/crates.io-index)"
+        ),
+        (
+            "ripgrep".to_owned(),
+            "14.1.0".to_owned(),
+            "crates.io".to_owned()
+        )
+    );
+    assert_eq!(
+        bins::split_package_id("tool 0.1.0 (path+file:///home/user/tool)").2,
+        "path"
+    );
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let home = tmp_dir.path();
+    fs::create_dir_all(home.join("bin")).unwrap();
+    fs::write(
+        home.join(".crates2.json"),
+        r#"{"installs":{
+            "ripgrep 14.1.0 (registry+https://github.com/rust-lang/crates.io-index)":{"bins":["rg"]},
+            "old-tool 0.1.0 (git+https://example.com/old-tool#abc)":{"bins":["old","old-helper"]}
+        }}"#,
+    )
+    .unwrap();
+    fs::write(home.join("bin/rg"), [0; 10]).unwrap();
+    for bin in ["old", "old-helper"] {
+        let file = fs::File::create(home.join("bin").join(bin)).unwrap();
+        file.set_len(20).unwrap();
+        let old = SystemTime::now() - Duration::from_hours(365 * 24);
+        file.set_times(fs::FileTimes::new().set_accessed(old).set_modified(old))
+            .unwrap();
+    }
+
+    // Run after it was installed
+    let file = fs::File::options()
+        .write(true)
+        .open(home.join("bin/rg"))
+        .unwrap();
+    let installed = SystemTime::now() - Duration::from_hours(24);
+    file.set_times(
+        fs::FileTimes::new()
+            .set_accessed(SystemTime::now())
+            .set_modified(installed),
+    )
+    .unwrap();
+
+    let tools = bins::tools(home, SizeMode::Apparent, false).unwrap();
+    assert_eq!(tools.len(), 2);
+    let old = tools.iter().find(|tool| tool.name == "old-tool").unwrap();
+    assert_eq!(old.source, "git");
+    assert_eq!(old.bins.len(), 2);
+    assert_eq!(old.size, 40);
+    // Not accessed since the installation, as on a `noatime` mount
+    assert_eq!(old.last_used, None);
+    let rg = tools.iter().find(|tool| tool.name == "ripgrep").unwrap();
+    assert!(rg.last_used.is_some());
+    let trusted = bins::tools(home, SizeMode::Apparent, true).unwrap();
+    let old = trusted.iter().find(|tool| tool.name == "old-tool").unwrap();
+    assert!(old.last_used.is_some());
+
+    // A copied binary keeps its older access time, but was not used before it was installed
+    file.set_times(
+        fs::FileTimes::new()
+            .set_accessed(SystemTime::now() - Duration::from_hours(2 * 365 * 24))
+            .set_modified(installed),
+    )
+    .unwrap();
+    let trusted = bins::tools(home, SizeMode::Apparent, true).unwrap();
+    let rg = trusted.iter().find(|tool| tool.name == "ripgrep").unwrap();
+    assert_eq!(rg.last_used, Some(installed));
+
+    for trust_atime in [false, true] {
+        let options = HomeOptions {
+            command: Some(HomeCommand::Bins {
+                prune_unused: Some(Duration::from_hours(180 * 24)),
+                trust_atime,
+            }),
+            dry_run: true,
+            ..Default::default()
+        };
+        home::run(&options, home).unwrap();
+        assert!(home.join("bin/old").exists());
+    }
+
+    // Without any metadata there is nothing to list
+    let empty = tempfile::tempdir().unwrap();
+    assert!(
+        bins::tools(empty.path(), SizeMode::Apparent, false)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_toolchain_names() {
+    assert_eq!(
+        toolchains::split_toolchain_name("nightly-2023-01-01-x86_64-unknown-linux-gnu"),
+        (
+            "nightly-2023-01-01".to_owned(),
+            Some("x86_64-unknown-linux-gnu".to_owned())
+        )
+    );
+    assert_eq!(
+        toolchains::split_toolchain_name("1.80"),
+        ("1.80".to_owned(), None)
+    );
+    assert!(toolchains::matches(
+        "stable",
+        "stable-x86_64-unknown-linux-gnu"
+    ));
+    assert!(!toolchains::matches(
+        "nightly",
+        "nightly-2023-01-01-x86_64-unknown-linux-gnu"
+    ));
+    assert!(!toolchains::matches(
+        "stable-aarch64-apple-darwin",
+        "stable-x86_64-unknown-linux-gnu"
+    ));
+}
+
+/// Creates projects pinning toolchains in `projects` and a rustup home in `rustup`
+fn toolchains_fixture(dir: &Path) -> (PathBuf, PathBuf) {
+    let root = dir.join("projects");
+    for (name, file, content) in [
+        (
+            "pinned",
+            "rust-toolchain.toml",
+            "[toolchain]\nchannel = \"nightly-2023-01-01\"\n",
+        ),
+        ("legacy", "rust-toolchain", "1.80\n"),
+        ("unpinned", "README.md", ""),
+        ("excluded", "rust-toolchain", "nightly-2023-06-01\n"),
+    ] {
+        let project = root.join(name);
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+        fs::write(project.join(file), content).unwrap();
+    }
+    let rustup_home = dir.join("rustup");
+    for name in [
+        "stable-x86_64-unknown-linux-gnu",
+        "beta-x86_64-unknown-linux-gnu",
+        "nightly-2023-01-01-x86_64-unknown-linux-gnu",
+        "nightly-2023-06-01-x86_64-unknown-linux-gnu",
+        "1.80-x86_64-unknown-linux-gnu",
+        "my-toolchain",
+    ] {
+        let dir = rustup_home.join("toolchains").join(name).join("bin");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("rustc"), [0; 10]).unwrap();
+    }
+    fs::write(
+        rustup_home.join("settings.toml"),
+        "version = \"12\"\ndefault_toolchain = \"stable-x86_64-unknown-linux-gnu\"\n\n\
+         [overrides]\n\"/work\" = \"beta-x86_64-unknown-linux-gnu\"\n",
+    )
+    .unwrap();
+    (root, rustup_home)
+}
+
+#[test]
+fn test_toolchains() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let (root, rustup_home) = toolchains_fixture(tmp_dir.path());
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(
+        tmp_dir.path(),
+        rustup_home.join("toolchains").join("stage1"),
+    )
+    .unwrap();
+
+    let dirs = UserDirs::in_dir(tmp_dir.path());
+    let list = ToolchainsOptions {
+        options: Options {
+            path: vec![root.clone()],
+            exclude: vec!["excluded".to_owned()],
+            no_cache: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let projects = list.search_options().scan(&dirs, false).unwrap();
+    assert_eq!(projects.len(), 3);
+
+    let installed = toolchains::toolchains(&projects, &list, &rustup_home);
+    let unreferenced: Vec<&str> = installed
+        .iter()
+        .filter(|toolchain| toolchain.unreferenced())
+        .map(|toolchain| toolchain.name.as_str())
+        .collect();
+    assert_eq!(
+        unreferenced,
+        ["nightly-2023-06-01-x86_64-unknown-linux-gnu"]
+    );
+    let find = |name: &str| {
+        installed
+            .iter()
+            .find(|toolchain| toolchain.name == name)
+            .unwrap()
+    };
+    assert_eq!(find("1.80-x86_64-unknown-linux-gnu").projects, ["legacy"]);
+    assert_eq!(
+        find("nightly-2023-01-01-x86_64-unknown-linux-gnu").projects,
+        ["pinned"]
+    );
+    assert_eq!(installed[0].size, 10);
+    // Custom toolchains are kept
+    assert!(find("my-toolchain").custom());
+    #[cfg(unix)]
+    assert!(find("stage1").linked);
+
+    // The pins of filtered projects still count when removing
+    let options = ToolchainsOptions {
+        remove_unreferenced: true,
+        yes: true,
+        ..list
+    };
+    let projects = options.search_options().scan(&dirs, false).unwrap();
+    assert_eq!(projects.len(), 4);
+    let installed = toolchains::toolchains(&projects, &options, &rustup_home);
+    assert!(installed.iter().all(|toolchain| !toolchain.unreferenced()));
+    toolchains::run(&projects, &options, &rustup_home, &dirs).unwrap();
+
+    // A project below the depth limit is not searched
+    fs::create_dir_all(root.join("group/deep")).unwrap();
+    fs::write(root.join("group/deep/Cargo.toml"), "").unwrap();
+    let error = toolchains::run(&projects, &options, &rustup_home, &dirs).unwrap_err();
+    assert!(error.to_string().contains("--max-depth 1"), "{error}");
+    let deeper = ToolchainsOptions {
+        options: Options {
+            max_depth: 2,
+            ..options.options.clone()
+        },
+        ..options
+    };
+    toolchains::run(&projects, &deeper, &rustup_home, &dirs).unwrap();
+
+    // Without a search path only the current directory would be checked for pins
+    let options = ToolchainsOptions {
+        options: Options::default(),
+        ..deeper
+    };
+    toolchains::run(&projects, &options, &rustup_home, &dirs).unwrap_err();
+}
+
+#[test]
+fn test_user_dirs() {
+    let vars = |vars: &'static [(&str, &str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| OsString::from(value))
+        }
+    };
+    let home = Path::new("/home/user");
+    let dirs = UserDirs::resolve(
+        vars(&[
+            ("XDG_CACHE_HOME", "/xdg/cache"),
+            ("XDG_CONFIG_HOME", "relative"),
+            ("XDG_DATA_HOME", "/xdg/data"),
+        ]),
+        Some(home),
+        false,
+    )
+    .unwrap();
+    assert_eq!(dirs.cache, Path::new("/xdg/cache/cargo-wash"));
+    // A relative XDG path is ignored
+    assert_eq!(dirs.config, home.join(".config/cargo-wash"));
+    assert_eq!(dirs.data, Path::new("/xdg/data/cargo-wash"));
+    assert_eq!(
+        UserDirs::resolve(vars(&[]), Some(home), false)
+            .unwrap()
+            .data,
+        home.join(".local/share/cargo-wash")
+    );
+
+    let dirs = UserDirs::resolve(
+        vars(&[("APPDATA", "/roaming"), ("LOCALAPPDATA", "/local")]),
+        Some(home),
+        true,
+    )
+    .unwrap();
+    assert_eq!(dirs.config, Path::new("/roaming/cargo-wash"));
+    assert_eq!(dirs.cache, Path::new("/local/cargo-wash"));
+    assert_eq!(dirs.data, Path::new("/roaming/cargo-wash"));
+    UserDirs::resolve(vars(&[]), Some(home), true).unwrap_err();
+    UserDirs::resolve(vars(&[]), None, false).unwrap_err();
+
+    // Without a path the roots registered in the given directories are scanned
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let dirs = UserDirs::in_dir(tmp_dir.path());
+    let root = tmp_dir.path().join("work");
+    fs::create_dir_all(root.join("app")).unwrap();
+    fs::write(root.join("app/Cargo.toml"), "").unwrap();
+    roots::run(&RootsCommand::Add { path: root }, &Config::path(&dirs)).unwrap();
+    let projects = Options::default().scan(&dirs, true).unwrap();
+    assert_eq!(projects.len(), 1);
+    assert!(Index::path(&dirs).exists());
+}
+
 #[test]
 fn test_commands() {
+    let home = tempfile::tempdir().unwrap();
+    let dirs = UserDirs::in_dir(home.path());
     let opts2 = Options {
-        path: PathBuf::from("/not_existing"),
+        path: vec![PathBuf::from("/not_existing")],
+        ..Default::default()
+    };
+    let stats = StatsOptions {
+        options: opts2.clone(),
         ..Default::default()
     };
-    assert!(cli::Commands::Stats(opts2.clone()).run().is_err());
-    assert!(cli::Commands::Clean(opts2).run().is_err());
+    assert!(cli::Commands::Stats(stats).run(&dirs).is_err());
+    assert!(cli::Commands::Clean(opts2).run(&dirs).is_err());
     let opts3 = Options {
-        path: PathBuf::from("."),
+        path: vec![PathBuf::from(".")],
         ..Default::default()
     };
-    cli::Commands::Clean(opts3).run().unwrap();
+    cli::Commands::Clean(opts3).run(&dirs).unwrap();
 }
 
 #[test]
 fn test_get_folder_size() {
-    let size = utility::get_folder_size("src").unwrap();
-    assert!(size > 0);
+    let size = utility::get_folder_size("src");
+    assert!(size.bytes > 0);
+    assert_eq!(size.errors, 0);
+    assert_eq!(
+        utility::get_folder_size("does-not-exist"),
+        FolderSize::default()
+    );
+}
+
+/// Compares two walks of the same folder, the order of the directories depends on the walk
+fn assert_same_size(mut a: FolderSize, mut b: FolderSize) {
+    a.dirs.sort();
+    b.dirs.sort();
+    assert_eq!(a, b, "Walkers disagree");
+}
+
+#[test]
+#[ignore = "Benchmark, run with `cargo test --release bench_folder_size -- --ignored --nocapture`"]
+fn bench_folder_size() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    for i in 0..50 {
+        for j in 0..20 {
+            let dir = tmp_dir
+                .path()
+                .join(format!("crate-{i}"))
+                .join(format!("deps-{j}"));
+            fs::create_dir_all(&dir).unwrap();
+            for k in 0..20 {
+                fs::write(dir.join(format!("file-{k}")), vec![0; k * 100]).unwrap();
+            }
+        }
+    }
+
+    // The median of several runs, the first run also warms up the file system cache
+    let median = |walk: &dyn Fn() -> FolderSize| {
+        let mut times: Vec<_> = (0..9)
+            .map(|_| {
+                let start = std::time::Instant::now();
+                walk();
+                start.elapsed()
+            })
+            .collect();
+        times.sort();
+        times[times.len() / 2]
+    };
+    let sequential = || utility::walk_sequential::<FolderSize>(tmp_dir.path());
+    let parallel = || utility::get_folder_size(tmp_dir.path());
+    assert_same_size(sequential(), parallel());
+
+    let sequential_time = median(&sequential);
+    let parallel_time = median(&parallel);
+    println!(
+        "sequential: {sequential_time:?}, parallel: {parallel_time:?} on {} threads",
+        rayon::current_num_threads()
+    );
+}
+
+#[test]
+fn test_get_folder_size_deep() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dir = tmp_dir.path().to_path_buf();
+    for i in 0..200 {
+        dir = dir.join(format!("{}", i % 10));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("file"), [0; 10]).unwrap();
+    }
+    let size = utility::get_folder_size(tmp_dir.path());
+    assert_eq!(size.bytes, 2000);
+    assert_same_size(
+        size.clone(),
+        utility::walk_sequential::<FolderSize>(tmp_dir.path()),
+    );
+
+    // Like `du`, the disk size includes the blocks of the directories
+    let dir_blocks: u64 = utility::dir_mtimes(tmp_dir.path())
+        .iter()
+        .map(|(dir, _)| utility::disk_usage(&fs::symlink_metadata(dir).unwrap()))
+        .sum();
+    let file_blocks: u64 = utility::list_files(tmp_dir.path())
+        .files
+        .iter()
+        .map(|file| file.disk_bytes)
+        .sum();
+    assert_eq!(size.disk_bytes, dir_blocks + file_blocks);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_size_modes() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let project = tmp_dir.path().join("sparse");
+    fs::create_dir_all(project.join("target")).unwrap();
+    fs::write(project.join("Cargo.toml"), "").unwrap();
+    fs::File::create(project.join("target").join("sparse.bin"))
+        .unwrap()
+        .set_len(64 * 1024 * 1024)
+        .unwrap();
+
+    let size = utility::get_folder_size(project.join("target"));
+    assert_eq!(size.get(SizeMode::Apparent), 64 * 1024 * 1024);
+    assert!(size.get(SizeMode::Disk) < size.get(SizeMode::Apparent));
+
+    let root = tmp_dir.path();
+    let filter = ProjectFilter::default();
+    let disk = utility::get_project(
+        &project,
+        root,
+        root,
+        &filter,
+        SizeMode::Disk,
+        &SizeCache::default(),
+        None,
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(disk.size, disk.disk_size);
+    assert_eq!(disk.apparent_size, 64 * 1024 * 1024);
+}
+
+#[test]
+fn test_size_cache() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let root = tmp_dir.path();
+    let project = root.join("app");
+    let deps = project.join("target/debug/deps");
+    fs::create_dir_all(&deps).unwrap();
+    fs::write(project.join("Cargo.toml"), "").unwrap();
+    fs::write(deps.join("libapp.rlib"), [0; 100]).unwrap();
+
+    let cache_path = root.join("cache/sizes.json");
+    let filter = ProjectFilter::default();
+    let get = |cache: &SizeCache| {
+        let cached = cache.get(&project.join("target"));
+        utility::get_project(
+            &project,
+            root,
+            root,
+            &filter,
+            SizeMode::Apparent,
+            cache,
+            cached,
+        )
+        .unwrap()
+        .unwrap()
+    };
+
+    let cache = SizeCache::load(&cache_path).unwrap();
+    let first = get(&cache);
+    assert!(!first.cached);
+    cache.save().unwrap();
+
+    let cache = SizeCache::load(&cache_path).unwrap();
+    let second = get(&cache);
+    assert!(second.cached);
+    assert_eq!(second.size, 100);
+
+    // A build adds files below existing directories, not only at the top level
+    fs::write(deps.join("app-1234"), [0; 50]).unwrap();
+    let third = get(&cache);
+    assert!(!third.cached);
+    assert_eq!(third.size, 150);
+
+    assert!(!get(&SizeCache::default()).cached);
+
+    // Entries written by another version were measured differently and are ignored
+    cache.save().unwrap();
+    let target = project.join("target");
+    assert!(SizeCache::load(&cache_path).unwrap().get(&target).is_some());
+    let content = fs::read_to_string(&cache_path).unwrap();
+    let old = content.replace(&format!("\"version\":{CACHE_VERSION}"), "\"version\":0");
+    assert_ne!(old, content, "The cache file has no version");
+    fs::write(&cache_path, old).unwrap();
+    assert!(SizeCache::load(&cache_path).unwrap().get(&target).is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_hard_links() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let root = tmp_dir.path();
+    for dir in ["a/target/debug/deps", "b/target/debug", "c/target"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    for project in ["a", "b", "c"] {
+        fs::write(root.join(project).join("Cargo.toml"), "").unwrap();
+    }
+    let binary = root.join("a/target/debug/deps/app-1234");
+    fs::write(&binary, [0; 1000]).unwrap();
+    fs::hard_link(&binary, root.join("a/target/debug/app")).unwrap();
+    fs::hard_link(&binary, root.join("b/target/debug/app")).unwrap();
+    fs::write(root.join("c/target/other"), [0; 10]).unwrap();
+
+    let size = utility::get_folder_size(root.join("a/target"));
+    assert_eq!(size.bytes, 1000);
+    assert_eq!(size.hardlinked_bytes, 1000);
+
+    let opts = Options {
+        path: vec![root.to_path_buf()],
+        ..Default::default()
+    };
+    let mut projects = opts.check_args(&UserDirs::in_dir(tmp_dir.path())).unwrap();
+    projects.sort_by_key(|p| p.name.clone());
+    let shares: Vec<_> = projects
+        .iter()
+        .map(|p| (p.size, p.hardlinked, p.shared))
+        .collect();
+    assert_eq!(shares, [(1000, 1000, 1000), (1000, 0, 1000), (10, 0, 0)]);
+    assert_eq!(total_size_of_projects(&projects), 1010);
+    assert_eq!(total_size_of_projects(&projects[1..]), 1010);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_get_folder_size_resilient() {
+    use std::os::unix::fs::{PermissionsExt as _, symlink};
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let dir = tmp_dir.path();
+    fs::write(dir.join("file"), [0; 100]).unwrap();
+    fs::create_dir_all(dir.join("locked")).unwrap();
+    fs::write(dir.join("locked").join("hidden"), [0; 50]).unwrap();
+    symlink(dir.join("missing"), dir.join("dangling")).unwrap();
+    symlink(env!("CARGO_MANIFEST_DIR"), dir.join("outside")).unwrap();
+    fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+
+    let size = utility::get_folder_size(dir);
+    fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+
+    // Symlinks count as links, not as their targets
+    let link_sizes = fs::symlink_metadata(dir.join("dangling")).unwrap().len()
+        + fs::symlink_metadata(dir.join("outside")).unwrap().len();
+    if fs::read_dir(dir.join("locked")).is_ok() {
+        // Running as root, permissions are not enforced
+        assert_eq!(size.bytes, 150 + link_sizes);
+    } else {
+        assert_eq!(size.bytes, 100 + link_sizes);
+        assert_eq!(size.errors, 1);
+    }
 }
 
 #[test]
 fn test_run_clean_excluded() {
     let opts = Options {
-        path: ".".into(),
+        path: vec![".".into()],
+        exclude: vec!["cargo-wash".to_owned()],
         ..Default::default()
     };
-    let projects = opts.check_args().unwrap();
-    let exclude = "cargo-wash, target".to_owned();
-    let result = clean::run(&projects, Some(&exclude));
+    let home = tempfile::tempdir().unwrap();
+    let projects = opts.check_args(&UserDirs::in_dir(home.path())).unwrap();
+    assert!(projects.is_empty(), "Excluded projects were not filtered");
+    let result = clean::run(&projects, SizeMode::Apparent, Units::Iec);
     assert!(result.is_ok(), "Test failed: {}", result.unwrap_err());
     assert!(
         *result.as_ref().unwrap() == 0,
@@ -147,10 +1520,368 @@ fn test_run_clean_excluded() {
     );
 }
 
+#[test]
+fn test_project_filter() {
+    let filter = ProjectFilter::new(
+        &["crates/*".to_owned()],
+        &["*-old".to_owned()],
+        &["^tmp".to_owned()],
+    )
+    .unwrap();
+    assert!(filter.is_match("core", "crates/core"));
+    assert!(!filter.is_match("core", "apps/core"));
+    assert!(!filter.is_match("core", "crates/core-old"));
+    assert!(!filter.is_match("tmp-core", "crates/tmp-core"));
+
+    let filter = ProjectFilter::new(&[], &["my-crate".to_owned()], &[]).unwrap();
+    assert!(!filter.is_match("my-crate", "some/dir"));
+    assert!(filter.is_match("other", "some/dir"));
+    assert!(ProjectFilter::default().is_match("any", "any"));
+
+    ProjectFilter::new(&["[".to_owned()], &[], &[]).unwrap_err();
+    ProjectFilter::new(&[], &[], &["(".to_owned()]).unwrap_err();
+}
+
+fn project_names(opts: &Options) -> Vec<String> {
+    let home = tempfile::tempdir().unwrap();
+    let mut names: Vec<_> = opts
+        .check_args(&UserDirs::in_dir(home.path()))
+        .unwrap()
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_package_identity() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let root = tmp_dir.path();
+    let manifests = [
+        (
+            "a/core",
+            "[package]\nname = \"alpha-core\"\nversion = \"1.2.3\"\n",
+        ),
+        (
+            "b/core",
+            "[package]\nname = \"beta-core\"\nversion.workspace = true\n[workspace.package]\nversion = \"0.4.0\"\n",
+        ),
+        ("ws", "[workspace]\nmembers = []\n"),
+    ];
+    for (dir, manifest) in manifests {
+        fs::create_dir_all(root.join(dir)).unwrap();
+        fs::write(root.join(dir).join("Cargo.toml"), manifest).unwrap();
+    }
+
+    let opts = Options {
+        path: vec![root.to_path_buf()],
+        max_depth: 2,
+        ..Default::default()
+    };
+    let mut projects = opts.check_args(&UserDirs::in_dir(tmp_dir.path())).unwrap();
+    projects.sort_by_key(|p| p.id.clone());
+    let identities: Vec<_> = projects
+        .iter()
+        .map(|p| (p.name.as_str(), p.id.as_str(), p.version.as_deref()))
+        .collect();
+    assert_eq!(
+        identities,
+        [
+            ("alpha-core", "a/core", Some("1.2.3")),
+            ("beta-core", "b/core", Some("0.4.0")),
+            ("ws", "ws", None),
+        ]
+    );
+
+    let opts = Options {
+        exclude: vec!["a/*".to_owned(), "ws".to_owned()],
+        ..opts
+    };
+    assert_eq!(project_names(&opts), ["beta-core"]);
+}
+
+#[test]
+fn test_washignore() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let root = tmp_dir.path();
+    for dir in ["keep", "ignored", "group/inner", "group/skip", "vendored"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+        fs::write(root.join(dir).join("Cargo.toml"), "").unwrap();
+    }
+    fs::write(root.join(".washignore"), "ignored/\n").unwrap();
+    fs::write(root.join("group").join(".washignore"), "skip\n").unwrap();
+    fs::write(root.join(".gitignore"), "vendored/\n").unwrap();
+
+    let opts = Options {
+        path: vec![root.to_path_buf()],
+        max_depth: 2,
+        ..Default::default()
+    };
+    assert_eq!(project_names(&opts), ["inner", "keep", "vendored"]);
+
+    let opts = Options {
+        gitignore: true,
+        ..opts
+    };
+    assert_eq!(project_names(&opts), ["inner", "keep"]);
+
+    let opts = Options {
+        gitignore: false,
+        no_ignore: true,
+        ..opts
+    };
+    assert_eq!(
+        project_names(&opts),
+        ["ignored", "inner", "keep", "skip", "vendored"]
+    );
+
+    let opts = Options {
+        max_depth: 1,
+        ..opts
+    };
+    assert_eq!(project_names(&opts), ["ignored", "keep", "vendored"]);
+}
+
+#[test]
+fn test_index_ignore_files() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let root = tmp_dir.path().join("work");
+    for dir in ["a", "group/b"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+        fs::write(root.join(dir).join("Cargo.toml"), "").unwrap();
+    }
+    fs::write(root.join(".washignore"), "a/\n").unwrap();
+    fs::write(root.join("group/.washignore"), "").unwrap();
+    let dirs = UserDirs::in_dir(tmp_dir.path());
+    let opts = Options {
+        path: vec![root.clone()],
+        max_depth: 2,
+        no_cache: true,
+        ..Default::default()
+    };
+    let names = || {
+        let mut names: Vec<_> = opts
+            .scan(&dirs, false)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        names.sort();
+        names
+    };
+    opts.scan(&dirs, true).unwrap();
+    assert_eq!(names(), ["b"]);
+
+    // Editing an ignore file in place does not change the mtime of its directory
+    let modified = index::modified(&root.join("group"));
+    fs::write(root.join("group/.washignore"), "b\n").unwrap();
+    fs::write(root.join(".washignore"), "").unwrap();
+    assert_eq!(index::modified(&root.join("group")), modified);
+    assert_eq!(names(), ["a"]);
+
+    // The hash is stored in the index, so it must be the same with every Rust release
+    assert_eq!(
+        index::ignore_hash(&root.join("group")),
+        Some(0xb0f7_f3e0_9907_5492)
+    );
+    assert_eq!(index::ignore_hash(&root.join("a")), None);
+}
+
+#[test]
+fn test_multiple_roots_ids() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let roots = ["work", "oss", "other/work"].map(|root| tmp_dir.path().join(root));
+    for root in &roots {
+        fs::create_dir_all(root.join("core")).unwrap();
+        fs::write(root.join("core/Cargo.toml"), "").unwrap();
+    }
+    let dirs = UserDirs::in_dir(tmp_dir.path());
+    let ids_matching = |paths: &[PathBuf], include: &[&str]| {
+        let opts = Options {
+            path: paths.to_vec(),
+            include: include.iter().map(|glob| (*glob).to_owned()).collect(),
+            no_cache: true,
+            ..Default::default()
+        };
+        let mut ids: Vec<_> = opts
+            .scan(&dirs, false)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        ids.sort();
+        ids
+    };
+    let ids = |paths: &[PathBuf]| ids_matching(paths, &[]);
+
+    Options {
+        path: vec![roots[0].clone()],
+        ..Default::default()
+    }
+    .scan(&dirs, true)
+    .unwrap();
+    assert_eq!(ids(&roots[..1]), ["core"]);
+    // The id from the index of the single root is not reused
+    assert_eq!(ids(&roots[..2]), ["oss/core", "work/core"]);
+
+    let mut expected: Vec<_> = [&roots[0], &roots[2]]
+        .map(|root| root.join("core").display().to_string())
+        .to_vec();
+    expected.insert(0, "oss/core".to_owned());
+    expected.sort();
+    assert_eq!(ids(&roots), expected);
+
+    // Filters match the path below the project's own root, whatever the number of roots
+    assert_eq!(
+        ids_matching(&roots[..2], &["core"]),
+        ["oss/core", "work/core"]
+    );
+    assert!(ids_matching(&roots[..2], &["work/*"]).is_empty());
+    // Also with the index of a single root
+    assert_eq!(ids_matching(&roots[..1], &["core"]), ["core"]);
+
+    let mut projects = vec![
+        Project::new("core", "/work/core", 1),
+        Project::new("core", "/oss/core", 2),
+    ];
+    projects[0].id = "work/core".to_owned();
+    projects[1].id = "oss/core".to_owned();
+    assert_eq!(stats::select(&projects, "oss/core").unwrap().size, 2);
+    stats::select(&projects, "core").unwrap_err();
+}
+
+#[test]
+fn test_roots_registry() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config_path = tmp_dir.path().join("config").join("config.toml");
+    let root = tmp_dir.path().join("work");
+    fs::create_dir_all(&root).unwrap();
+
+    let add = RootsCommand::Add { path: root.clone() };
+    roots::run(&add, &config_path).unwrap();
+    roots::run(&add, &config_path).unwrap();
+    let config = Config::load(&config_path).unwrap();
+    assert_eq!(config.roots, [fs::canonicalize(&root).unwrap()]);
+    roots::run(&RootsCommand::List, &config_path).unwrap();
+
+    roots::run(&RootsCommand::Remove { path: root.clone() }, &config_path).unwrap();
+    assert!(Config::load(&config_path).unwrap().roots.is_empty());
+    roots::run(&RootsCommand::Remove { path: root }, &config_path).unwrap_err();
+
+    let missing = RootsCommand::Add {
+        path: tmp_dir.path().join("missing"),
+    };
+    roots::run(&missing, &config_path).unwrap_err();
+}
+
+#[test]
+fn test_multiple_roots_dedup() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let root = tmp_dir.path();
+    for dir in ["work/a", "work/b", "oss/c"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+        fs::write(root.join(dir).join("Cargo.toml"), "").unwrap();
+    }
+
+    let opts = Options {
+        path: vec![
+            root.join("work"),
+            root.join("oss"),
+            root.join("work").join("a"),
+        ],
+        ..Default::default()
+    };
+    assert_eq!(project_names(&opts), ["a", "b", "c"]);
+}
+
+#[test]
+fn test_project_index() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let root = tmp_dir.path().join("root");
+    let dirs = UserDirs::in_dir(tmp_dir.path());
+    let index_path = Index::path(&dirs);
+    for dir in ["a/target", "b/target"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    fs::write(root.join("a").join("Cargo.toml"), "").unwrap();
+    fs::write(root.join("b").join("Cargo.toml"), "").unwrap();
+    fs::write(root.join("a").join("target").join("out"), [0; 100]).unwrap();
+
+    let opts = Options {
+        path: vec![root.clone()],
+        ..Default::default()
+    };
+    assert_eq!(opts.scan(&dirs, true).unwrap().len(), 2);
+
+    // Unchanged entries are served from the index
+    let mut index = Index::load(&index_path).unwrap();
+    index.roots[0]
+        .projects
+        .iter_mut()
+        .for_each(|p| p.size = 4096);
+    index.save(&index_path).unwrap();
+    let sizes = |opts: &Options| {
+        let mut projects = opts.scan(&dirs, false).unwrap();
+        projects.sort_by_key(|p| p.name.clone());
+        projects.iter().map(|p| p.size).collect::<Vec<_>>()
+    };
+    assert_eq!(sizes(&opts), [4096, 4096]);
+
+    // Changed target dirs and new projects are picked up
+    fs::write(root.join("b").join("target").join("out"), [0; 10]).unwrap();
+    fs::create_dir_all(root.join("c")).unwrap();
+    fs::write(root.join("c").join("Cargo.toml"), "").unwrap();
+    assert_eq!(sizes(&opts), [4096, 10, 0]);
+
+    let refresh = Options {
+        refresh: true,
+        ..opts
+    };
+    assert_eq!(sizes(&refresh), [100, 10, 0]);
+}
+
+#[test]
+fn test_projects_from() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let root = tmp_dir.path();
+    fs::create_dir_all(root.join("rust")).unwrap();
+    fs::create_dir_all(root.join("other")).unwrap();
+    fs::write(root.join("rust").join("Cargo.toml"), "").unwrap();
+
+    let list = root.join("projects.txt");
+    fs::write(
+        &list,
+        format!(
+            "# projects\n{}\n\n{}\n{}\n{}\n",
+            root.join("rust").display(),
+            root.join("other").display(),
+            root.join("missing").display(),
+            root.join("rust").display(),
+        ),
+    )
+    .unwrap();
+    assert_eq!(utility::read_project_list(&list).unwrap().len(), 4);
+
+    let opts = Options {
+        projects_from: Some(list),
+        ..Default::default()
+    };
+    assert_eq!(project_names(&opts), ["rust"]);
+
+    let opts = Options {
+        projects_from: Some(root.join("missing.txt")),
+        ..Default::default()
+    };
+    opts.check_args(&UserDirs::in_dir(tmp_dir.path()))
+        .unwrap_err();
+}
+
 #[test]
 fn clean_test() {
     // create example project
     let tmp_dir = tempfile::tempdir().unwrap();
+    let dirs = UserDirs::in_dir(tmp_dir.path());
     let example_project = tmp_dir.path().join("example_project");
 
     fs::create_dir_all(&example_project).unwrap();
@@ -167,18 +1898,18 @@ fn clean_test() {
         .unwrap();
 
     let opts = Options {
-        path: example_project,
+        path: vec![example_project],
         ..Default::default()
     };
 
     let command = cli::Commands::Clean(opts);
-    command.run().context("Could not run command").unwrap();
+    command.run(&dirs).context("Could not run command").unwrap();
     // utils::run_clean(&projects, false, Some(&exclude))?;
 }
 
 fn generate_test_opts(dir: &Path) -> Options {
     Options {
-        path: dir.to_path_buf(),
+        path: vec![dir.to_path_buf()],
         ..Default::default()
     }
 }
@@ -187,6 +1918,7 @@ fn generate_test_opts(dir: &Path) -> Options {
 fn execute_test() {
     // create example project
     let tmp_dir = tempfile::tempdir().unwrap();
+    let dirs = UserDirs::in_dir(tmp_dir.path());
     let example_project = tmp_dir.path().join("example_project");
 
     fs::create_dir_all(&example_project).unwrap();
@@ -203,37 +1935,42 @@ fn execute_test() {
         .unwrap();
 
     let clean_cmd = Commands::Clean(generate_test_opts(&example_project));
-    clean_cmd.run().unwrap();
+    clean_cmd.run(&dirs).unwrap();
 
     let build_cmd = Commands::Build(generate_test_opts(&example_project));
-    build_cmd.run().unwrap();
+    build_cmd.run(&dirs).unwrap();
 
     let test_cmd = Commands::Test(generate_test_opts(&example_project));
-    test_cmd.run().unwrap();
+    test_cmd.run(&dirs).unwrap();
 
     let doc_cmd = Commands::Doc(generate_test_opts(&example_project));
-    doc_cmd.run().unwrap();
+    doc_cmd.run(&dirs).unwrap();
 
     let run_cmd = Commands::Run(generate_test_opts(&example_project));
-    run_cmd.run().unwrap();
+    run_cmd.run(&dirs).unwrap();
 
     let test_cmd = Commands::Update(generate_test_opts(&example_project));
-    test_cmd.run().unwrap();
+    test_cmd.run(&dirs).unwrap();
 
     let bench_cmd = Commands::Bench(generate_test_opts(&example_project));
-    bench_cmd.run().unwrap();
+    bench_cmd.run(&dirs).unwrap();
 
     let update_cmd = Commands::Update(generate_test_opts(&example_project));
-    update_cmd.run().unwrap();
+    update_cmd.run(&dirs).unwrap();
 
-    let stats_cmd = Commands::Stats(generate_test_opts(&example_project));
-    stats_cmd.run().unwrap();
+    let stats_cmd = Commands::Stats(StatsOptions {
+        options: generate_test_opts(&example_project),
+        long: true,
+        ..Default::default()
+    });
+    stats_cmd.run(&dirs).unwrap();
 }
 
 #[test]
 fn execute_test_failures() {
     // create example project
     let tmp_dir = tempfile::tempdir().unwrap();
+    let dirs = UserDirs::in_dir(tmp_dir.path());
     let example_project = tmp_dir.path().join("example_project");
 
     fs::create_dir_all(&example_project).unwrap();
@@ -252,84 +1989,97 @@ fn execute_test_failures() {
     std::fs::remove_file(example_project.join("src").join("main.rs")).unwrap();
 
     let clean_cmd = Commands::Clean(generate_test_opts(&example_project));
-    clean_cmd.run().unwrap();
+    clean_cmd.run(&dirs).unwrap();
 
     let build_cmd = Commands::Build(generate_test_opts(&example_project));
-    build_cmd.run().unwrap_err();
+    build_cmd.run(&dirs).unwrap_err();
 
     let test_cmd = Commands::Test(generate_test_opts(&example_project));
-    test_cmd.run().unwrap_err();
+    test_cmd.run(&dirs).unwrap_err();
 
     let doc_cmd = Commands::Doc(generate_test_opts(&example_project));
-    doc_cmd.run().unwrap_err();
+    doc_cmd.run(&dirs).unwrap_err();
 
     let run_cmd = Commands::Run(generate_test_opts(&example_project));
-    run_cmd.run().unwrap_err();
+    run_cmd.run(&dirs).unwrap_err();
 
     let test_cmd = Commands::Update(generate_test_opts(&example_project));
-    test_cmd.run().unwrap_err();
+    test_cmd.run(&dirs).unwrap_err();
 
     let bench_cmd = Commands::Bench(generate_test_opts(&example_project));
-    bench_cmd.run().unwrap_err();
+    bench_cmd.run(&dirs).unwrap_err();
 
     let update_cmd = Commands::Update(generate_test_opts(&example_project));
-    update_cmd.run().unwrap_err();
+    update_cmd.run(&dirs).unwrap_err();
 
-    let stats_cmd = Commands::Stats(generate_test_opts(&example_project));
-    stats_cmd.run().unwrap();
+    let stats_cmd = Commands::Stats(StatsOptions {
+        options: generate_test_opts(&example_project),
+        long: true,
+        ..Default::default()
+    });
+    stats_cmd.run(&dirs).unwrap();
 }
 
 #[test]
-fn test_size_in_bytes() {
-    let size_b = Size::new(500.0, SizeUnit::B);
-    assert_eq!(size_b.size_in_bytes(), 500);
-
-    let size_kb = Size::new(1.0, SizeUnit::KB);
-    assert_eq!(size_kb.size_in_bytes(), 1024);
-
-    let size_mb = Size::new(1.0, SizeUnit::MB);
-    assert_eq!(size_mb.size_in_bytes(), 1024 * 1024);
-
-    let size_gb = Size::new(1.0, SizeUnit::GB);
-    assert_eq!(size_gb.size_in_bytes(), 1024 * 1024 * 1024);
+fn test_unit_multiplier() {
+    assert_eq!(SizeUnit::B.multiplier(), 1);
+    assert_eq!(SizeUnit::KiB.multiplier(), 1024);
+    assert_eq!(SizeUnit::MiB.multiplier(), 1024 * 1024);
+    assert_eq!(SizeUnit::GiB.multiplier(), 1024 * 1024 * 1024);
+    assert_eq!(SizeUnit::TiB.multiplier(), 1 << 40);
+    assert_eq!(SizeUnit::KB.multiplier(), 1000);
+    assert_eq!(SizeUnit::TB.multiplier(), 1_000_000_000_000);
 }
 
 #[test]
 fn test_to_size() {
-    let size = Size::to_size(500);
+    let size = Size::with_units(500, Units::Iec);
     assert_eq!(size.unit, SizeUnit::B);
     assert_eq!(size.value, 500.0);
 
-    let size = Size::to_size(1024);
-    assert_eq!(size.unit, SizeUnit::KB);
+    let size = Size::with_units(1024, Units::Iec);
+    assert_eq!(size.unit, SizeUnit::KiB);
     assert_eq!(size.value, 1.0);
 
-    let size = Size::to_size(1024 * 1024);
-    assert_eq!(size.unit, SizeUnit::MB);
+    let size = Size::with_units(1024 * 1024, Units::Iec);
+    assert_eq!(size.unit, SizeUnit::MiB);
     assert_eq!(size.value, 1.0);
 
-    let size = Size::to_size(1024 * 1024 * 1024);
-    assert_eq!(size.unit, SizeUnit::GB);
+    let size = Size::with_units(1024 * 1024 * 1024, Units::Iec);
+    assert_eq!(size.unit, SizeUnit::GiB);
     assert_eq!(size.value, 1.0);
+
+    let size = Size::with_units(3 << 40, Units::Iec);
+    assert_eq!(size.unit, SizeUnit::TiB);
+    assert_eq!(size.value, 3.0);
 }
 
 #[test]
-fn test_display() {
-    let size = Size::new(1.2345, SizeUnit::MB);
-    assert_eq!(format!("{size}"), "1.23 MB");
+fn test_with_units() {
+    let size = Size::with_units(1_500_000, Units::Si);
+    assert_eq!(size.unit, SizeUnit::MB);
+    assert_eq!(size.value, 1.5);
 
-    let size = Size::new(2048.0, SizeUnit::B);
-    assert_eq!(format!("{size}"), "2048.00 B");
+    let size = Size::with_units(999, Units::Si);
+    assert_eq!(size.unit, SizeUnit::B);
+
+    let size = Size::with_units(2_000_000_000_000, Units::Si);
+    assert_eq!(size.unit, SizeUnit::TB);
+
+    let size = Size::with_units(123_456_789, Units::Bytes);
+    assert_eq!(format!("{size}"), "123456789 B");
 }
 
 #[test]
-fn test_round_trip_conversion() {
-    let original = Size::new(1.5, SizeUnit::MB);
-    let bytes = original.size_in_bytes();
-    let converted = Size::to_size(bytes);
+fn test_display() {
+    let size = Size::new(1.2345, SizeUnit::MiB);
+    assert_eq!(format!("{size}"), "1.23 MiB");
+
+    let size = Size::new(2048.0, SizeUnit::B);
+    assert_eq!(format!("{size}"), "2048 B");
 
-    assert_eq!(converted.unit, SizeUnit::MB);
-    assert!((converted.value - 1.5).abs() < 0.01);
+    let size = Size::new(1.5, SizeUnit::KB);
+    assert_eq!(format!("{size}"), "1.50 kB");
 }
 
 #[test]
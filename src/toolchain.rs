@@ -0,0 +1,207 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{manifest::Manifest, utility};
+
+/// The toolchain a project asks for and the compilers its artifacts were built with
+#[derive(Serialize, Clone, Default, PartialEq, Eq, Debug)]
+pub struct Toolchain {
+    /// Channel from `rust-toolchain.toml` or `rust-toolchain` in the project or a parent directory
+    pub pinned: Option<String>,
+    /// `rust-version` from `Cargo.toml`
+    pub rust_version: Option<String>,
+    /// Releases of the compilers in `target/.rustc_info.json`, like `1.80.0`
+    pub built_with: Vec<String>,
+    /// Number of different compilers in the fingerprints of the target directory
+    pub compilers: usize,
+    /// Whether artifacts were built with another toolchain than the pinned one,
+    /// they are rebuilt by the next build anyway. `None` if it cannot be decided:
+    /// the fingerprints only hold a hash of the compiler, which is not mapped to a release.
+    pub mismatch: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct ToolchainFile {
+    toolchain: ToolchainSection,
+}
+
+#[derive(Deserialize)]
+struct ToolchainSection {
+    channel: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RustcInfo {
+    outputs: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct Fingerprint {
+    rustc: u64,
+}
+
+impl Toolchain {
+    /// Reads the toolchain information of the project in `project`
+    pub fn read(project: &Path) -> Self {
+        let target_dir = project.join("target");
+        let built_with = rustc_releases(&target_dir);
+        let compilers = fingerprint_compilers(&target_dir).max(built_with.len());
+        let pinned = pinned_channel(project);
+        let mismatch = pinned.as_deref().map_or(Some(false), |channel| {
+            let matches: Vec<Option<bool>> = built_with
+                .iter()
+                .map(|release| matches_channel(channel, release))
+                .collect();
+            if matches.contains(&Some(false)) {
+                Some(true)
+            } else if compilers == 0 {
+                // Nothing was built
+                Some(false)
+            } else if built_with.is_empty()
+                || compilers > built_with.len()
+                || matches.contains(&None)
+            {
+                // Fingerprints of other compilers may be left over from before the pin
+                None
+            } else {
+                Some(false)
+            }
+        });
+
+        Self {
+            pinned,
+            rust_version: Manifest::read(project)
+                .ok()
+                .and_then(|manifest| manifest.rust_version().map(str::to_owned)),
+            built_with,
+            compilers,
+            mismatch,
+        }
+    }
+
+    /// Formats the compilers the artifacts were built with, like `1.80.0, 1 other (unknown)`
+    pub fn built_with_label(&self) -> String {
+        let mut parts = self.built_with.clone();
+        let others = self.compilers - self.built_with.len();
+        if others > 0 {
+            parts.push(format!("{others} other"));
+        }
+        let label = if parts.is_empty() {
+            "-".to_owned()
+        } else {
+            parts.join(", ")
+        };
+        match self.mismatch {
+            Some(true) => format!("{label} (mismatch)"),
+            Some(false) => label,
+            None => format!("{label} (unknown)"),
+        }
+    }
+}
+
+/// Returns the channel pinned for a project, rustup uses the nearest toolchain file
+pub fn pinned_channel(project: &Path) -> Option<String> {
+    project.ancestors().find_map(|dir| {
+        ["rust-toolchain", "rust-toolchain.toml"]
+            .iter()
+            .find_map(|name| fs::read_to_string(dir.join(name)).ok())
+            .and_then(|content| parse_toolchain_file(&content))
+    })
+}
+
+/// Parses a toolchain file, either TOML or the legacy format with only the channel
+pub fn parse_toolchain_file(content: &str) -> Option<String> {
+    if let Ok(file) = toml::from_str::<ToolchainFile>(content) {
+        return file.toolchain.channel;
+    }
+    content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.contains(['=', '[']))
+        .map(str::to_owned)
+}
+
+/// Reads the releases from the `rustc -vV` outputs cargo caches in `.rustc_info.json`
+fn rustc_releases(target_dir: &Path) -> Vec<String> {
+    let Some(info) = fs::read_to_string(target_dir.join(".rustc_info.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str::<RustcInfo>(&content).ok())
+    else {
+        return vec![];
+    };
+
+    let mut releases: Vec<String> = info
+        .outputs
+        .values()
+        .filter_map(|output| output.get("stdout")?.as_str())
+        .flat_map(str::lines)
+        .filter_map(|line| line.strip_prefix("release: "))
+        .map(|release| release.trim().to_owned())
+        .collect();
+    releases.sort();
+    releases.dedup();
+    releases
+}
+
+/// Counts the different compilers recorded in the fingerprints of every profile,
+/// reading one fingerprint per unit
+fn fingerprint_compilers(target_dir: &Path) -> usize {
+    let mut fingerprint_dirs = vec![];
+    for name in utility::subdirs(target_dir) {
+        let dir = target_dir.join(name);
+        if dir.join(".fingerprint").is_dir() {
+            fingerprint_dirs.push(dir.join(".fingerprint"));
+        } else {
+            // Profiles of a target triple
+            fingerprint_dirs.extend(
+                utility::subdirs(&dir)
+                    .into_iter()
+                    .map(|profile| dir.join(profile).join(".fingerprint"))
+                    .filter(|fingerprint_dir| fingerprint_dir.is_dir()),
+            );
+        }
+    }
+
+    let mut compilers = HashSet::new();
+    for fingerprint_dir in fingerprint_dirs {
+        for unit in utility::subdirs(&fingerprint_dir) {
+            let Ok(entries) = fs::read_dir(fingerprint_dir.join(unit)) else {
+                continue;
+            };
+            let rustc = entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .find_map(|path| {
+                    let content = fs::read_to_string(path).ok()?;
+                    serde_json::from_str::<Fingerprint>(&content).ok()
+                });
+            if let Some(fingerprint) = rustc {
+                compilers.insert(fingerprint.rustc);
+            }
+        }
+    }
+    compilers.len()
+}
+
+/// Returns whether a rustc release like `1.80.0` or `1.82.0-nightly` belongs to a channel
+/// like `1.80`, `stable` or `nightly-2024-07-01`, `None` for custom toolchains
+pub fn matches_channel(channel: &str, release: &str) -> Option<bool> {
+    let (version, pre_release) = release.split_once('-').unwrap_or((release, ""));
+    if channel.starts_with(|c: char| c.is_ascii_digit()) {
+        let pinned = channel
+            .split_once('-')
+            .map_or(channel, |(version, _)| version);
+        return Some(
+            pre_release.is_empty()
+                && (version == pinned || version.starts_with(&format!("{pinned}."))),
+        );
+    }
+    match channel.split_once('-').map_or(channel, |(name, _)| name) {
+        "stable" => Some(pre_release.is_empty()),
+        "beta" => Some(pre_release.starts_with("beta")),
+        "nightly" => Some(pre_release == "nightly"),
+        _ => None,
+    }
+}
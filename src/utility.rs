@@ -1,11 +1,24 @@
+use core::ops::AddAssign;
 use std::{
-    env, fs,
-    path::{Path, PathBuf},
+    env,
+    ffi::OsString,
+    fs,
+    io::{self, IsTerminal as _},
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime},
 };
 
 use anyhow::Context as _;
+use parking_lot::Mutex;
+use rayon::Scope;
 
-use crate::{data::Project, extensions::PathBufExt as _};
+use crate::{
+    cache::SizeCache,
+    data::{FileEntry, FileList, FolderSize, Inode, Project, SizeMode},
+    extensions::PathBufExt as _,
+    filter::ProjectFilter,
+    manifest::Manifest,
+};
 
 pub fn sanitize_path_input(dir: &PathBuf) -> anyhow::Result<PathBuf> {
     if dir == Path::new("/") || dir == Path::new(".") {
@@ -22,48 +35,485 @@ pub fn sanitize_path_input(dir: &PathBuf) -> anyhow::Result<PathBuf> {
     }
 }
 
-/// Recursively calculate the size of a folder
-pub fn get_folder_size<P: AsRef<Path>>(dir: P) -> anyhow::Result<u64> {
-    if !dir.as_ref().exists() {
-        return Ok(0);
+/// Reads newline-separated paths from a file, or from stdin if the path is `-`.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn read_project_list(source: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let content = if source == Path::new("-") {
+        io::read_to_string(io::stdin()).context("Failed to read project list from stdin")?
+    } else {
+        fs::read_to_string(source)
+            .context(format!("Failed to read project list: {}", source.display()))?
+    };
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Parses a duration like `30d`, `12h`, `2w` or `45m`
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration `{value}`, expected e.g. 30d, 12h or 2w"))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "Invalid duration unit in `{value}`, expected s, m, h, d or w"
+            ));
+        }
+    };
+    Ok(Duration::from_secs(number * seconds))
+}
+
+/// Formats how long ago a time was, like `3 days ago`
+pub fn format_age(time: Option<SystemTime>, now: SystemTime) -> String {
+    let Some(time) = time else {
+        return "never".to_owned();
+    };
+    let seconds = now.duration_since(time).unwrap_or_default().as_secs();
+    let (count, unit) = match seconds {
+        0..60 => return "just now".to_owned(),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86_400 => (seconds / 3600, "hour"),
+        86_400..2_592_000 => (seconds / 86_400, "day"),
+        2_592_000..31_536_000 => (seconds / 2_592_000, "month"),
+        _ => (seconds / 31_536_000, "year"),
+    };
+    if count == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{count} {unit}s ago")
+    }
+}
+
+/// Formats a duration with its largest unit, like `3d` or `5h`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..60 => format!("{seconds}s"),
+        60..3600 => format!("{}m", seconds / 60),
+        3600..86_400 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86_400),
     }
-    let mut total_size = 0;
+}
+
+/// The per-user directories of cargo-wash. Commands get them passed in,
+/// so tests can keep their files away from the ones of the user.
+#[derive(Clone, Debug)]
+pub struct UserDirs {
+    /// Holds the configuration, like the registered roots
+    pub config: PathBuf,
+    /// Holds data that can be recreated, like the project index and the size cache
+    pub cache: PathBuf,
+    /// Holds data that cannot be recreated, like the size history
+    pub data: PathBuf,
+}
 
-    for entry_res in fs::read_dir(dir)? {
-        let entry = entry_res?;
-        let path = entry.path();
-        let metadata = fs::metadata(&path)?;
+impl UserDirs {
+    /// Resolves the directories of the current user
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::resolve(
+            |name| env::var_os(name),
+            home::home_dir().as_deref(),
+            cfg!(windows),
+        )
+    }
+
+    /// Resolves the directories from the variables returned by `var`, following the
+    /// XDG base directory spec below `home` on Unix and using `%APPDATA%` and
+    /// `%LOCALAPPDATA%` on Windows
+    pub fn resolve(
+        var: impl Fn(&str) -> Option<OsString>,
+        home: Option<&Path>,
+        windows: bool,
+    ) -> anyhow::Result<Self> {
+        let user_dir = |xdg_var: &str, home_fallback: &str, windows_var: &str| {
+            let base = if windows {
+                var(windows_var).map(PathBuf::from)
+            } else {
+                // Relative paths are invalid according to the spec
+                var(xdg_var)
+                    .map(PathBuf::from)
+                    .filter(|dir| dir.is_absolute())
+                    .or_else(|| home.map(|home| home.join(home_fallback)))
+            };
+            base.map(|dir| dir.join("cargo-wash"))
+                .context("Failed to determine the user directory")
+        };
+        Ok(Self {
+            config: user_dir("XDG_CONFIG_HOME", ".config", "APPDATA")?,
+            cache: user_dir("XDG_CACHE_HOME", ".cache", "LOCALAPPDATA")?,
+            data: user_dir("XDG_DATA_HOME", ".local/share", "APPDATA")?,
+        })
+    }
 
-        if metadata.is_dir() {
-            total_size += get_folder_size(&path)?;
-        } else {
-            total_size += metadata.len();
+    /// Places all directories below `base`, like a temporary directory of a test
+    #[cfg(test)]
+    pub fn in_dir(base: &Path) -> Self {
+        Self {
+            config: base.join("config"),
+            cache: base.join("cache"),
+            data: base.join("data"),
         }
     }
+}
 
-    Ok(total_size)
+/// Returns the cargo home directory, `$CARGO_HOME` or `~/.cargo`
+pub fn cargo_home() -> anyhow::Result<PathBuf> {
+    home::cargo_home().context("Failed to locate the cargo home directory")
 }
 
-pub fn get_project(
-    path: &PathBuf,
-    exclude_folder: Option<&String>,
-) -> anyhow::Result<Option<Project>> {
-    if let Some(exclude) = exclude_folder {
-        if path.to_string_lossy().contains(exclude) {
-            log::debug!("Excluding folder: {exclude}");
-            return Ok(None);
+/// Returns the rustup home directory, `$RUSTUP_HOME` or `~/.rustup`
+pub fn rustup_home() -> anyhow::Result<PathBuf> {
+    home::rustup_home().context("Failed to locate the rustup home directory")
+}
+
+/// Asks the user to confirm an action, `yes` skips the question.
+/// Fails if the question cannot be asked because stdin is not a terminal.
+#[expect(
+    clippy::print_stderr,
+    reason = "The question must not end up in the output"
+)]
+pub fn confirm(question: &str, yes: bool) -> anyhow::Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    anyhow::ensure!(
+        io::stdin().is_terminal(),
+        "Cannot ask for confirmation without a terminal, pass --yes to proceed"
+    );
+    eprint!("{question} [y/N] ");
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read the answer")?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Configures the global thread pool shared by all project scans and size walks
+pub fn init_thread_pool(threads: usize) {
+    if let Err(error) = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+    {
+        log::debug!("Thread pool already initialized: {error}");
+    }
+}
+
+/// Receives the entries found by `walk`. Every directory is collected
+/// separately and then merged into the total with `AddAssign`.
+pub trait Collector: Default + AddAssign + Send {
+    fn collect_dir(&mut self, dir: &Path, metadata: &fs::Metadata);
+    fn collect_file(&mut self, path: PathBuf, metadata: &fs::Metadata);
+    fn collect_error(&mut self);
+}
+
+impl Collector for FolderSize {
+    fn collect_dir(&mut self, dir: &Path, metadata: &fs::Metadata) {
+        self.dirs
+            .push((dir.to_path_buf(), metadata.modified().ok()));
+        // Like `du`, the blocks of the directories themselves are used space too
+        self.disk_bytes += disk_usage(metadata);
+    }
+
+    fn collect_file(&mut self, _path: PathBuf, metadata: &fs::Metadata) {
+        self.add_file(inode(metadata), metadata.len(), disk_usage(metadata));
+        self.newest = self.newest.max(metadata.modified().ok());
+    }
+
+    fn collect_error(&mut self) {
+        self.errors += 1;
+    }
+}
+
+impl Collector for FileList {
+    fn collect_dir(&mut self, _dir: &Path, _metadata: &fs::Metadata) {}
+
+    fn collect_file(&mut self, path: PathBuf, metadata: &fs::Metadata) {
+        self.files.push(FileEntry {
+            path,
+            inode: inode(metadata),
+            bytes: metadata.len(),
+            disk_bytes: disk_usage(metadata),
+        });
+    }
+
+    fn collect_error(&mut self) {
+        self.errors += 1;
+    }
+}
+
+/// Calculate the size of a folder, see `walk`
+pub fn get_folder_size<P: AsRef<Path>>(dir: P) -> FolderSize {
+    walk(dir.as_ref())
+}
+
+/// Lists the files below a folder with their size, see `walk`
+pub fn list_files(dir: &Path) -> FileList {
+    walk(dir)
+}
+
+/// Walk a folder and collect its entries.
+/// Directories are walked in parallel on the global thread pool, each one as
+/// a separate job, so one big folder is spread over all threads and deep trees
+/// do not grow the stack. Symlinks are not followed, and entries that cannot be
+/// read are skipped and counted as errors instead of failing the whole walk.
+fn walk<C: Collector>(dir: &Path) -> C {
+    let total = Mutex::new(C::default());
+    let Ok(metadata) = fs::symlink_metadata(dir) else {
+        return total.into_inner();
+    };
+
+    rayon::scope(|scope| walk_dir(scope, dir, &metadata, &total));
+    total.into_inner()
+}
+
+fn walk_dir<'scope, C: Collector>(
+    scope: &Scope<'scope>,
+    dir: &Path,
+    metadata: &fs::Metadata,
+    total: &'scope Mutex<C>,
+) {
+    let mut collector = C::default();
+    collector.collect_dir(dir, metadata);
+
+    match fs::read_dir(dir) {
+        Ok(entries) => {
+            for entry_res in entries {
+                let metadata = entry_res.and_then(|entry| {
+                    let path = entry.path();
+                    fs::symlink_metadata(&path).map(|metadata| (path, metadata))
+                });
+
+                match metadata {
+                    Ok((path, metadata)) if metadata.is_dir() => {
+                        scope.spawn(move |scope| walk_dir(scope, &path, &metadata, total));
+                    }
+                    Ok((path, metadata)) => collector.collect_file(path, &metadata),
+                    Err(error) => {
+                        log::debug!("Failed to read entry in {}: {error}", dir.display());
+                        collector.collect_error();
+                    }
+                }
+            }
+        }
+        Err(error) => {
+            log::debug!("Failed to read {}: {error}", dir.display());
+            collector.collect_error();
         }
-        log::debug!("Checking folder: {}", path.to_string_lossy());
     }
 
-    let name = &path.get_name()?;
-    let size = get_folder_size(path.join("target"))?;
+    *total.lock() += collector;
+}
+
+/// The sequential, recursive walk that `walk` replaced, collecting the same entries.
+/// Kept as the baseline of the `bench_folder_size` benchmark.
+#[cfg(test)]
+pub fn walk_sequential<C: Collector>(dir: &Path) -> C {
+    let mut collector = C::default();
+    let Ok(metadata) = fs::symlink_metadata(dir) else {
+        return collector;
+    };
+    collector.collect_dir(dir, &metadata);
+
+    match fs::read_dir(dir) {
+        Ok(entries) => {
+            for entry_res in entries {
+                let metadata = entry_res.and_then(|entry| {
+                    let path = entry.path();
+                    fs::symlink_metadata(&path).map(|metadata| (path, metadata))
+                });
+
+                match metadata {
+                    Ok((path, metadata)) if metadata.is_dir() => {
+                        collector += walk_sequential(&path);
+                    }
+                    Ok((path, metadata)) => collector.collect_file(path, &metadata),
+                    Err(error) => {
+                        log::debug!("Failed to read entry in {}: {error}", dir.display());
+                        collector.collect_error();
+                    }
+                }
+            }
+        }
+        Err(error) => {
+            log::debug!("Failed to read {}: {error}", dir.display());
+            collector.collect_error();
+        }
+    }
+    collector
+}
+
+/// Lists the directories below `dir`, including itself, with their mtime.
+/// Only directories are stat'ed, which makes this much cheaper than a size walk.
+pub fn dir_mtimes(dir: &Path) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut dirs = vec![];
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(metadata) = fs::symlink_metadata(&dir) else {
+            continue;
+        };
+        if let Ok(entries) = fs::read_dir(&dir) {
+            pending.extend(
+                entries
+                    .flatten()
+                    .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+                    .map(|entry| entry.path()),
+            );
+        }
+        dirs.push((dir, metadata.modified().ok()));
+    }
+    dirs
+}
+
+/// Lists the names of the subdirectories of a directory
+pub fn subdirs(dir: &Path) -> Vec<String> {
+    fs::read_dir(dir).map_or_else(
+        |_| vec![],
+        |entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect()
+        },
+    )
+}
+
+/// Returns the bytes allocated on disk for a file
+#[cfg(unix)]
+pub fn disk_usage(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt as _;
+
+    // `st_blocks` is always counted in 512 byte units
+    metadata.blocks() * 512
+}
+
+/// Returns the bytes allocated on disk for a file
+#[cfg(not(unix))]
+pub fn disk_usage(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+/// Returns the inode of a file with several hard links
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> Option<Inode> {
+    use std::os::unix::fs::MetadataExt as _;
+
+    (metadata.nlink() > 1).then(|| Inode {
+        dev: metadata.dev(),
+        ino: metadata.ino(),
+    })
+}
+
+/// Returns the inode of a file with several hard links
+#[cfg(not(unix))]
+const fn inode(_metadata: &fs::Metadata) -> Option<Inode> {
+    None
+}
+
+/// Returns the display id of a project: its path relative to `root`, joined with `/`.
+/// A project at `root` itself uses its directory name.
+pub fn project_id(path: &PathBuf, root: &Path) -> anyhow::Result<String> {
+    let parts: Vec<_> = path
+        .strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            Component::Prefix(_)
+            | Component::RootDir
+            | Component::CurDir
+            | Component::ParentDir => None,
+        })
+        .collect();
+
+    if parts.is_empty() {
+        path.get_name()
+    } else {
+        Ok(parts.join("/"))
+    }
+}
+
+/// Reads the project at `path` if it is a Rust project that passes the filter.
+/// The filter is matched against the path relative to the search `root`,
+/// the id is the path relative to `id_base`, which is above the root with several roots.
+/// `cached` is the size of the target directory from `cache`, looked up by the caller,
+/// otherwise the size is calculated and stored in `cache`.
+pub fn get_project(
+    path: &PathBuf,
+    root: &Path,
+    id_base: &Path,
+    filter: &ProjectFilter,
+    size_mode: SizeMode,
+    cache: &SizeCache,
+    cached: Option<FolderSize>,
+) -> anyhow::Result<Option<Project>> {
+    log::debug!("Checking folder: {}", path.to_string_lossy());
+    let dir_name = &path.get_name()?;
 
     // Skip non Rust projects
     if !path.join("Cargo.toml").exists() {
-        log::debug!("Skipping non-Rust project: {name}");
+        log::debug!("Skipping non-Rust project: {dir_name}");
         return Ok(None);
     }
 
-    Ok(Some(Project::new(name, path, size)))
+    let manifest = Manifest::read(path).unwrap_or_else(|error| {
+        log::warn!("{error:#}");
+        Manifest::default()
+    });
+    // Virtual workspaces have no package name
+    let name = manifest.name().unwrap_or(dir_name);
+    if !filter.is_match(name, &project_id(path, root)?) {
+        log::debug!("Excluding folder: {}", path.to_string_lossy());
+        return Ok(None);
+    }
+
+    let id = project_id(path, id_base)?;
+    let target_dir = path.join("target");
+    let is_cached = cached.is_some();
+    let size = cached.unwrap_or_else(|| {
+        let size = get_folder_size(&target_dir);
+        cache.insert(&target_dir, &size);
+        size
+    });
+    if size.errors > 0 {
+        log::warn!(
+            "Size of {id} is partial, {} entries could not be read",
+            size.errors
+        );
+    }
+
+    let mut project = Project::new(name, path, size.get(size_mode));
+    project.apparent_size = size.bytes;
+    project.disk_size = size.disk_bytes;
+    project.hardlinked = size.hardlinked(size_mode);
+    project.linked = size
+        .linked
+        .iter()
+        .map(|(inode, (bytes, disk_bytes))| match size_mode {
+            SizeMode::Apparent => (*inode, *bytes),
+            SizeMode::Disk => (*inode, *disk_bytes),
+        })
+        .collect();
+    project.size_errors = size.errors;
+    project.cached = is_cached;
+    project.last_built = size.newest;
+    project.id = id;
+    project.root = Some(root.to_path_buf());
+    project.version = manifest.version().map(str::to_owned);
+    Ok(Some(project))
 }
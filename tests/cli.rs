@@ -0,0 +1,48 @@
+use std::{fs, process::Command};
+
+/// Runs the real binary, the unit tests in `src/test.rs` cannot see what ends up on stdout
+#[test]
+fn test_json_stdout() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let project = tmp_dir.path().join("app");
+    fs::create_dir_all(project.join("target")).unwrap();
+    fs::write(
+        project.join("Cargo.toml"),
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+    fs::write(project.join("target/file"), [0; 10]).unwrap();
+    // The missing path makes the scan warn
+    let list = tmp_dir.path().join("list.txt");
+    fs::write(
+        &list,
+        format!(
+            "{}\n{}\n",
+            project.display(),
+            tmp_dir.path().join("missing").display()
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cargo-wash"))
+        .args([
+            "wash",
+            "stats",
+            "--format",
+            "json",
+            "--record",
+            "--projects-from",
+        ])
+        .arg(&list)
+        .env("XDG_CACHE_HOME", tmp_dir.path().join("cache"))
+        .env("XDG_CONFIG_HOME", tmp_dir.path().join("config"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(json.to_string().contains("\"app\""), "{json}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Skipped 1 of 2"), "{stderr}");
+    assert!(stderr.contains("Recorded"), "{stderr}");
+}